@@ -0,0 +1,41 @@
+//! Throughput of the format closure for each preset, with and without colors
+//!
+//! Run with `cargo bench`. Records are written to a file in the temporary directory.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rimplog::{log_scoped, ColorMode, LoggerBuilder, LoggerPreset, NamedLogger, PathDepth, PathFallback};
+use std::hint::black_box;
+
+fn presets(c: &mut Criterion) {
+    let path = std::env::temp_dir().join("rimplog-bench.log");
+    let mut group = c.benchmark_group("preset");
+    for preset in [LoggerPreset::FULL, LoggerPreset::THREAD, LoggerPreset::SIMPLE] {
+        for color in [ColorMode::Always, ColorMode::Never] {
+            let logger = NamedLogger::new(
+                "bench",
+                LoggerBuilder {
+                    preset,
+                    color: Some(color),
+                    file: Some(path.clone()),
+                    ..LoggerBuilder::default()
+                },
+            );
+            let id = BenchmarkId::new(format!("{:?}", preset), format!("{:?}", color));
+            group.bench_function(id, |b| {
+                b.iter(|| log_scoped!(logger, log::Level::Info, "request {} took {}ms", black_box(42), black_box(7)))
+            });
+        }
+    }
+    group.finish();
+    let _ = std::fs::remove_file(path);
+}
+
+fn project_relative_path(c: &mut Criterion) {
+    let file = "/home/user/.cargo/registry/src/index/hyper-1.0.0/src/proto/h1/conn.rs";
+    c.bench_function("project_relative_path", |b| {
+        b.iter(|| rimplog::__project_relative_path(black_box(file), PathDepth::Components(2), PathFallback::FileNameOnly))
+    });
+}
+
+criterion_group!(benches, presets, project_relative_path);
+criterion_main!(benches);
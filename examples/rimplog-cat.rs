@@ -0,0 +1,37 @@
+//! Print a log file written with the `BINARY` preset as text
+//!
+//! Usage: `cargo run --example rimplog-cat --features binary -- <file>`
+
+use rimplog::BinaryRecord;
+use std::fs::File;
+use std::io::BufReader;
+use std::{env, process};
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: rimplog-cat <file>");
+        process::exit(1);
+    };
+    let file = File::open(&path).unwrap_or_else(|err| {
+        eprintln!("Failed to open '{}': {}", path, err);
+        process::exit(1);
+    });
+
+    let mut reader = BufReader::new(file);
+    loop {
+        match BinaryRecord::decode(&mut reader) {
+            Ok(Some(record)) => println!(
+                "{} {:<5} [{}] {}",
+                record.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level,
+                record.target,
+                record.message
+            ),
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("Failed to decode '{}': {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+use std::io::{self, Read};
+
+/// A record of the `BINARY` preset
+///
+/// Each record is written as a little-endian `u32` length of the rest of the record, followed by
+/// - the timestamp in milliseconds since the Unix epoch as an `i64`
+/// - the level as a `u8` (`1` is `Error` through `5` is `Trace`)
+/// - the target and the message, each as a `u32` length followed by UTF-8 bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryRecord {
+    pub timestamp: DateTime<Utc>,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl BinaryRecord {
+    /// Append the encoded record to `buf`
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let len = 8 + 1 + 4 + self.target.len() + 4 + self.message.len();
+        buf.reserve(4 + len);
+        buf.extend_from_slice(&(len as u32).to_le_bytes());
+        buf.extend_from_slice(&self.timestamp.timestamp_millis().to_le_bytes());
+        buf.push(self.level as u8);
+        for field in [&self.target, &self.message] {
+            buf.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            buf.extend_from_slice(field.as_bytes());
+        }
+    }
+
+    /// Read the next record from `reader`, returns `None` at the end of the input
+    pub fn decode<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        // Read what is there instead of allocating the declared length up front, which corrupt input
+        // could put at 4 GiB
+        let len = u64::from(u32::from_le_bytes(len));
+        let mut body = Vec::new();
+        if reader.take(len).read_to_end(&mut body)? as u64 != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated record"));
+        }
+
+        let mut body = body.as_slice();
+        let timestamp = i64::from_le_bytes(take(&mut body, 8)?.try_into().unwrap());
+        let timestamp = DateTime::from_timestamp_millis(timestamp).ok_or_else(|| invalid_data("timestamp out of range"))?;
+        let level = match take(&mut body, 1)?[0] {
+            1 => log::Level::Error,
+            2 => log::Level::Warn,
+            3 => log::Level::Info,
+            4 => log::Level::Debug,
+            5 => log::Level::Trace,
+            _ => return Err(invalid_data("invalid level")),
+        };
+        let target = take_string(&mut body)?;
+        let message = take_string(&mut body)?;
+
+        Ok(Some(BinaryRecord {
+            timestamp,
+            level,
+            target,
+            message,
+        }))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn take<'a>(body: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if body.len() < len {
+        return Err(invalid_data("truncated record"));
+    }
+    let (field, rest) = body.split_at(len);
+    *body = rest;
+    Ok(field)
+}
+
+fn take_string(body: &mut &[u8]) -> io::Result<String> {
+    let len = u32::from_le_bytes(take(body, 4)?.try_into().unwrap()) as usize;
+    String::from_utf8(take(body, len)?.to_vec()).map_err(|_| invalid_data("invalid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: log::Level, message: &str) -> BinaryRecord {
+        BinaryRecord {
+            timestamp: DateTime::from_timestamp_millis(1_729_215_000_123).unwrap(),
+            level,
+            target: "my_app::db".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = [record(log::Level::Error, "connection lost"), record(log::Level::Trace, "héllo\nworld")];
+        let mut buf = Vec::new();
+        for record in &records {
+            record.encode(&mut buf);
+        }
+        let mut reader = buf.as_slice();
+        for record in &records {
+            assert_eq!(BinaryRecord::decode(&mut reader).unwrap().as_ref(), Some(record));
+        }
+        assert_eq!(BinaryRecord::decode(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_records_are_invalid_data() {
+        let mut buf = Vec::new();
+        record(log::Level::Info, "ready").encode(&mut buf);
+        buf.pop();
+        let err = BinaryRecord::decode(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut buf = Vec::new();
+        record(log::Level::Info, "ready").encode(&mut buf);
+        buf[12] = 9;
+        let err = BinaryRecord::decode(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn declared_lengths_past_the_input_are_not_allocated() {
+        // A corrupt length of almost 4 GiB followed by a few bytes
+        let mut buf = u32::MAX.to_le_bytes().to_vec();
+        buf.extend_from_slice(b"garbage");
+        let err = BinaryRecord::decode(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Keep the first records of every target, then only sample them
+///
+/// Noisy startups are kept in full for debugging, while the steady state that follows is
+/// throttled. Records are counted per target, whatever their level, once they passed the level filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BurstPolicy {
+    /// Number of records of a target that are all kept
+    pub initial: usize,
+    /// Keep one record in this many after `initial`, `0` drops them all
+    pub then_every: u32,
+}
+
+/// Applies a [`BurstPolicy`], counting the records of every target
+pub(crate) struct BurstSampler {
+    policy: BurstPolicy,
+    counts: Mutex<HashMap<String, usize>>,
+}
+
+impl BurstSampler {
+    pub(crate) fn new(policy: BurstPolicy) -> Self {
+        BurstSampler {
+            policy,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a record of `target`, and get whether it's kept
+    pub(crate) fn admit(&self, target: &str) -> bool {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = match counts.get_mut(target) {
+            Some(count) => count,
+            None => counts.entry(target.to_string()).or_insert(0),
+        };
+        let seen = *count;
+        *count = count.saturating_add(1);
+        if seen < self.policy.initial {
+            return true;
+        }
+        match self.policy.then_every {
+            0 => false,
+            every => (seen - self.policy.initial).is_multiple_of(every as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_first_records_then_samples_per_target() {
+        let sampler = BurstSampler::new(BurstPolicy {
+            initial: 3,
+            then_every: 4,
+        });
+        let kept: Vec<bool> = (0..12).map(|_| sampler.admit("app::db")).collect();
+        let expected = [true, true, true, true, false, false, false, true, false, false, false, true];
+        assert_eq!(kept, expected);
+        // Another target starts its own burst
+        assert!(sampler.admit("app::http"));
+    }
+
+    #[test]
+    fn then_every_zero_drops_everything_after_the_burst() {
+        let sampler = BurstSampler::new(BurstPolicy {
+            initial: 2,
+            then_every: 0,
+        });
+        let kept: Vec<bool> = (0..5).map(|_| sampler.admit("app")).collect();
+        assert_eq!(kept, [true, true, false, false, false]);
+    }
+}
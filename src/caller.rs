@@ -0,0 +1,22 @@
+use std::fmt;
+use std::panic::Location;
+
+use log::{Level, Metadata, Record};
+
+/// Log a record located at `location` instead of the macro call, used by the `log_*_caller` macros
+#[doc(hidden)]
+pub fn __log_caller(level: Level, target: &'static str, location: &'static Location<'static>, args: fmt::Arguments) {
+    let metadata = Metadata::builder().level(level).target(target).build();
+    if level > log::STATIC_MAX_LEVEL || level > log::max_level() || !log::logger().enabled(&metadata) {
+        return;
+    }
+    log::logger().log(
+        &Record::builder()
+            .metadata(metadata)
+            .args(args)
+            .module_path_static(Some(target))
+            .file_static(Some(location.file()))
+            .line(Some(location.line()))
+            .build(),
+    );
+}
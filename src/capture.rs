@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io;
+
+/// Target of the records re-emitted from stderr by `capture_stderr`
+#[cfg(unix)]
+const STDERR_TARGET: &str = "stderr";
+
+/// Redirect file descriptor 2 into a pipe whose lines are logged as warnings
+///
+/// Returns the original stderr, which the logger must write to instead of file descriptor 2
+/// so its own lines aren't captured again.
+#[cfg(unix)]
+pub(crate) fn capture_stderr() -> io::Result<File> {
+    use std::io::BufRead;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::thread;
+
+    const STDERR_FILENO: i32 = 2;
+
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors `pipe` writes
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: both descriptors were just opened and are owned by nothing else
+    let (reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    // SAFETY: `dup` has no memory safety requirements
+    let original = unsafe { libc::dup(STDERR_FILENO) };
+    if original < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the new descriptor is open and owned by nothing else
+    let original = unsafe { File::from_raw_fd(original) };
+    // SAFETY: both descriptors are open, `dup2` replaces file descriptor 2 atomically
+    if unsafe { libc::dup2(writer.as_raw_fd(), STDERR_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // File descriptor 2 keeps the pipe open from now on
+    drop(writer);
+
+    thread::Builder::new()
+        .name("rimplog-stderr-capture".to_string())
+        .spawn(move || {
+            for line in io::BufReader::new(reader).lines() {
+                match line {
+                    // The logging macros of this crate end messages with a newline too
+                    Ok(line) => log::warn!(target: STDERR_TARGET, "{}\n", line),
+                    Err(_) => break,
+                }
+            }
+        })?;
+    Ok(original)
+}
+
+/// Capturing stderr relies on file descriptors, which other platforms don't have
+#[cfg(not(unix))]
+pub(crate) fn capture_stderr() -> io::Result<File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "capture_stderr is only supported on Unix"))
+}
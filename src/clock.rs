@@ -0,0 +1,57 @@
+use chrono::{DateTime, FixedOffset, Local};
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local! {
+    /// Time of the record being logged by the `log_*_at_time` macros on this thread
+    static RECORD_TIME: Cell<Option<DateTime<FixedOffset>>> = const { Cell::new(None) };
+}
+
+/// Source of the timestamps written with each record
+///
+/// Replace it with [`LoggerBuilder::clock`](crate::LoggerBuilder::clock), for example with
+/// [`FixedClock`](crate::testing::FixedClock) to get deterministic output in tests.
+pub trait Clock: Send + Sync {
+    /// Get the current time
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+/// The real clock, in the local time zone
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+}
+
+/// Get the time of the record being logged, the one given to a `log_*_at_time` macro or else `clock`'s
+pub(crate) fn record_time(clock: &dyn Clock) -> DateTime<FixedOffset> {
+    RECORD_TIME.with(|time| time.get()).unwrap_or_else(|| clock.now())
+}
+
+/// Run `log` with `time` as the time of the records it logs, used by the `log_*_at_time` macros
+#[doc(hidden)]
+pub fn __log_at_time(time: impl Into<DateTime<FixedOffset>>, log: impl FnOnce()) {
+    let previous = RECORD_TIME.with(|current| current.replace(Some(time.into())));
+    log();
+    RECORD_TIME.with(|current| current.set(previous));
+}
+
+/// Get the CPU time used by all threads of the process so far, for `show_cpu_time`
+#[cfg(unix)]
+pub(crate) fn process_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `time` is a valid timespec for the call to write to
+    if unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+/// The CPU time relies on `clock_gettime`, which other platforms don't have
+#[cfg(not(unix))]
+pub(crate) fn process_cpu_time() -> Option<Duration> {
+    None
+}
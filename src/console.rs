@@ -0,0 +1,236 @@
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+
+static ANSI_SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+/// Escape moving to the start of the line and clearing it, written before records by `progress_aware`
+pub(crate) const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Draws a progress bar again after a record was printed over it, see `progress_aware`
+pub type RedrawCallback = Arc<dyn Fn() + Send + Sync>;
+
+/// Runs the console write of a record while the progress bars are hidden, see `progress_suspend`
+pub type SuspendCallback = Arc<dyn Fn(&mut dyn FnMut()) + Send + Sync>;
+
+/// Get a `progress_suspend` callback hiding the progress bars of `bars` while each record is written
+///
+/// The bars are drawn again below the record, so logs and bars don't overwrite each other.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::LoggerBuilder;
+///
+/// let bars = indicatif::MultiProgress::new();
+/// rimplog::init_logger(LoggerBuilder {
+///     progress_suspend: Some(rimplog::indicatif_suspend(&bars)),
+///     ..LoggerBuilder::default()
+/// });
+/// let bar = bars.add(indicatif::ProgressBar::new(100));
+/// ```
+#[cfg(feature = "indicatif")]
+pub fn indicatif_suspend(bars: &indicatif::MultiProgress) -> SuspendCallback {
+    let bars = bars.clone();
+    Arc::new(move |write| bars.suspend(write))
+}
+
+/// Enable ANSI escapes on the console, disabling colors if the console doesn't support them
+///
+/// Legacy Windows consoles print escapes as garbage unless virtual terminal processing
+/// is turned on, other platforms always support them.
+pub(crate) fn init_ansi_support() -> bool {
+    *ANSI_SUPPORTED.get_or_init(|| disable_colors_unless(enable_virtual_terminal()))
+}
+
+#[cfg(windows)]
+fn enable_virtual_terminal() -> bool {
+    colored::control::set_virtual_terminal(true).is_ok()
+}
+
+#[cfg(not(windows))]
+fn enable_virtual_terminal() -> bool {
+    true
+}
+
+/// Disable colors unless enabling ANSI escapes succeeded, returning whether they are supported
+fn disable_colors_unless(enabled: bool) -> bool {
+    if !enabled {
+        colored::control::set_override(false);
+    }
+    enabled
+}
+
+/// Make the `colored` crate follow `mode`, when a logger is installed or reconfigured
+///
+/// `Always` forces colors, and the other modes go back to detecting them, unless the
+/// console doesn't support ANSI escapes.
+pub(crate) fn apply_color_override(mode: ColorMode) {
+    if mode == ColorMode::Always {
+        colored::control::set_override(true);
+    } else if ansi_supported() {
+        colored::control::unset_override();
+    } else {
+        colored::control::set_override(false);
+    }
+}
+
+/// Colors forced on for a test, until dropped
+///
+/// The override of `colored` is global, so the tests forcing it take turns and put it back.
+#[cfg(test)]
+pub(crate) struct ForcedColors {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+pub(crate) fn force_colors() -> ForcedColors {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    colored::control::set_override(true);
+    ForcedColors { _lock: lock }
+}
+
+#[cfg(test)]
+impl Drop for ForcedColors {
+    fn drop(&mut self) {
+        colored::control::unset_override();
+    }
+}
+
+/// Whether the console supports ANSI escapes, as detected by `init_logger`
+///
+/// When this is `false`, colors have been disabled. Before the logger is initialized
+/// this always returns `true`.
+pub fn ansi_supported() -> bool {
+    ANSI_SUPPORTED.get().copied().unwrap_or(true)
+}
+
+static HYPERLINKS_SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+/// Whether the terminal renders OSC 8 hyperlinks, for `hyperlink_paths`
+///
+/// Terminals that don't support them may print the escapes, so only the known ones are
+/// detected. `FORCE_HYPERLINK=1` or `0` overrides the detection.
+pub(crate) fn hyperlinks_supported() -> bool {
+    *HYPERLINKS_SUPPORTED.get_or_init(|| {
+        if let Ok(force) = std::env::var("FORCE_HYPERLINK") {
+            return force.trim() != "0";
+        }
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        if std::env::var_os("WT_SESSION").is_some() || std::env::var_os("KONSOLE_VERSION").is_some() {
+            return true;
+        }
+        if var("VTE_VERSION").parse::<u32>().is_ok_and(|version| version >= 5000) {
+            return true;
+        }
+        matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper")
+            || ["kitty", "alacritty", "foot", "wezterm"].iter().any(|term| var("TERM").contains(term))
+    })
+}
+
+/// When to write colors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Only when writing to a terminal, respecting `NO_COLOR` and `CLICOLOR_FORCE`
+    #[default]
+    Auto,
+    /// Always, even when writing to a file or pipe
+    Always,
+    /// Never
+    Never,
+}
+
+/// How records written to stdout are buffered, see `stdout_buffering`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StdoutBuffering {
+    /// Flushed at the end of every line, for immediate feedback in interactive tools
+    Line,
+    /// Flushed when the buffer is full, on `rimplog::flush()` and when the guard is dropped, for throughput
+    Block,
+    /// Flushed after every record, even those without a trailing newline
+    None,
+}
+
+/// Every write of records to stdout, buffered as set by `stdout_buffering`
+pub(crate) struct StdoutSink {
+    buffering: StdoutBuffering,
+    block: Mutex<BufWriter<io::Stdout>>,
+}
+
+impl StdoutSink {
+    /// Create the sink, line-buffered on a terminal and block-buffered otherwise unless `explicit` is set
+    pub(crate) fn new(explicit: Option<StdoutBuffering>) -> Self {
+        let buffering = explicit.unwrap_or_else(|| {
+            if io::stdout().is_terminal() {
+                StdoutBuffering::Line
+            } else {
+                StdoutBuffering::Block
+            }
+        });
+        StdoutSink {
+            buffering,
+            block: Mutex::new(BufWriter::new(io::stdout())),
+        }
+    }
+
+    pub(crate) fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        match self.buffering {
+            // `Stdout` is line-buffered already
+            StdoutBuffering::Line => io::stdout().lock().write_all(bytes),
+            StdoutBuffering::Block => self.block.lock().unwrap_or_else(|e| e.into_inner()).write_all(bytes),
+            StdoutBuffering::None => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(bytes)?;
+                stdout.flush()
+            }
+        }
+    }
+
+    pub(crate) fn flush(&self) {
+        let _ = self.block.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Resolve the color mode, an explicit setting takes precedence over `RIMPLOG_COLOR`, which takes
+/// precedence over auto detection
+pub(crate) fn color_mode(explicit: Option<ColorMode>) -> ColorMode {
+    if let Some(mode) = explicit {
+        return mode;
+    }
+    let Ok(value) = std::env::var("RIMPLOG_COLOR") else {
+        return ColorMode::Auto;
+    };
+    match value.trim().to_lowercase().as_str() {
+        "always" => ColorMode::Always,
+        "never" => ColorMode::Never,
+        "auto" | "" => ColorMode::Auto,
+        _ => {
+            eprintln!("Invalid RIMPLOG_COLOR '{}', expected always, never or auto, using auto", value);
+            ColorMode::Auto
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_color_mode_ignores_the_environment() {
+        for mode in [ColorMode::Always, ColorMode::Never, ColorMode::Auto] {
+            assert_eq!(color_mode(Some(mode)), mode);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn failing_to_enable_ansi_disables_colors() {
+        let _colors = force_colors();
+        assert!(!disable_colors_unless(false));
+        assert!(!colored::control::SHOULD_COLORIZE.should_colorize());
+
+        colored::control::set_override(true);
+        assert!(disable_colors_unless(true));
+        assert!(colored::control::SHOULD_COLORIZE.should_colorize());
+    }
+}
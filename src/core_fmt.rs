@@ -0,0 +1,167 @@
+use core::fmt::{self, Write};
+use log::{Level, Record};
+
+use crate::LoggerPreset;
+
+/// A formatter for `no_std` targets that writes records to any `core::fmt::Write` sink
+///
+/// It only relies on `core` and `log`, so it needs neither env_logger, chrono nor an allocator.
+/// Instead of wall-clock time, the caller passes a monotonic tick count, and since there
+/// are no thread names, `THREAD` shows no thread segment.
+pub struct CoreFormatter {
+    preset: LoggerPreset,
+    path_depth: usize,
+    colors: bool,
+    pad_level: bool,
+}
+
+impl CoreFormatter {
+    /// Create a formatter using the layout of `preset`
+    ///
+    /// Presets without a text layout are formatted like `FULL`.
+    pub const fn new(preset: LoggerPreset) -> Self {
+        CoreFormatter {
+            preset,
+            path_depth: 0,
+            colors: false,
+            pad_level: true,
+        }
+    }
+
+    /// Set the path display depth, `0` shows the full path from `src`
+    pub const fn path_depth(mut self, path_depth: usize) -> Self {
+        self.path_depth = path_depth;
+        self
+    }
+
+    /// Set whether to color the level label with ANSI escapes
+    pub const fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Set whether to pad level labels to five columns, after the brackets around them
+    pub const fn pad_level(mut self, pad_level: bool) -> Self {
+        self.pad_level = pad_level;
+        self
+    }
+
+    /// Write `record` to `sink`, the message is written as is, without adding a newline
+    pub fn format<W: Write>(&self, sink: &mut W, ticks: u64, record: &Record) -> fmt::Result {
+        match self.preset {
+            LoggerPreset::THREAD => {
+                write!(sink, "{} ", ticks)?;
+                let padding = self.write_level(sink, record.level())?;
+                write!(sink, "{:padding$} {}", "", record.args())
+            }
+            LoggerPreset::SIMPLE => {
+                write!(sink, "[ {} ", ticks)?;
+                let padding = self.write_level(sink, record.level())?;
+                write!(sink, "]{:padding$} {}", "", record.args())
+            }
+            LoggerPreset::RAW => write!(sink, "{}", record.args()),
+            _ => {
+                write!(sink, "{} ", ticks)?;
+                let padding = self.write_level(sink, record.level())?;
+                write!(sink, "{:padding$}", "")?;
+                let path = relative_path(record.file().unwrap_or("unknown"), self.path_depth);
+                write!(sink, " [{}:{}] {}", path, record.line().unwrap_or(0), record.args())
+            }
+        }
+    }
+
+    /// Write the label of `level`, returning the padding the caller writes after it
+    fn write_level<W: Write>(&self, sink: &mut W, level: Level) -> Result<usize, fmt::Error> {
+        let (label, color) = match level {
+            Level::Error => ("ERROR", "31"),
+            Level::Warn => ("WARN", "33"),
+            Level::Info => ("INFO", "32"),
+            Level::Debug => ("DEBUG", "34"),
+            Level::Trace => ("TRACE", "35"),
+        };
+        if self.colors {
+            write!(sink, "\x1b[1;{}m{}\x1b[0m", color, label)?;
+        } else {
+            sink.write_str(label)?;
+        }
+        Ok(if self.pad_level { 5 - label.len() } else { 0 })
+    }
+}
+
+/// Get the path starting at the `src` directory, keeping the last `depth` components
+fn relative_path(file_path: &str, depth: usize) -> &str {
+    let path = if file_path.starts_with("src/") {
+        file_path
+    } else {
+        match file_path.find("/src/") {
+            Some(index) => &file_path[index + 1..],
+            None => file_path,
+        }
+    };
+    if depth == 0 {
+        return path;
+    }
+    let mut start = path.len();
+    for _ in 0..depth {
+        match path[..start].rfind('/') {
+            Some(index) => start = index,
+            None => return path,
+        }
+    }
+    &path[start + 1..]
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+
+    use super::*;
+
+    fn format(formatter: CoreFormatter, level: Level, file: &str) -> String {
+        let mut out = String::new();
+        formatter
+            .format(
+                &mut out,
+                1234,
+                &Record::builder()
+                    .args(format_args!("ready\n"))
+                    .level(level)
+                    .file(Some(file))
+                    .line(Some(42))
+                    .build(),
+            )
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn presets_write_their_layout() {
+        let file = "/home/user/app/src/db/pool.rs";
+        assert_eq!(format(CoreFormatter::new(LoggerPreset::SIMPLE), Level::Info, file), "[ 1234 INFO]  ready\n");
+        assert_eq!(format(CoreFormatter::new(LoggerPreset::THREAD), Level::Warn, file), "1234 WARN  ready\n");
+        assert_eq!(
+            format(CoreFormatter::new(LoggerPreset::FULL), Level::Error, file),
+            "1234 ERROR [src/db/pool.rs:42] ready\n"
+        );
+        assert_eq!(format(CoreFormatter::new(LoggerPreset::RAW), Level::Debug, file), "ready\n");
+    }
+
+    #[test]
+    fn options_change_the_output() {
+        let file = "src/db/pool.rs";
+        assert_eq!(
+            format(CoreFormatter::new(LoggerPreset::FULL).path_depth(1), Level::Info, file),
+            "1234 INFO  [pool.rs:42] ready\n"
+        );
+        assert_eq!(
+            format(CoreFormatter::new(LoggerPreset::SIMPLE).pad_level(false), Level::Info, file),
+            "[ 1234 INFO] ready\n"
+        );
+        assert_eq!(
+            format(CoreFormatter::new(LoggerPreset::THREAD).colors(true), Level::Info, file),
+            "1234 \x1b[1;32mINFO\x1b[0m  ready\n"
+        );
+    }
+}
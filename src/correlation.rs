@@ -0,0 +1,39 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_CORRELATION_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static CORRELATION_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Get a correlation id that no other call in this process returned, starting at 1
+pub fn new_correlation_id() -> u64 {
+    NEXT_CORRELATION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Get the correlation id of the current thread, set by [`with_correlation!`](crate::with_correlation)
+pub(crate) fn correlation_id() -> Option<u64> {
+    CORRELATION_ID.with(|id| id.get())
+}
+
+/// Guard of a [`with_correlation!`](crate::with_correlation) scope, restores the previous id when dropped
+pub struct CorrelationGuard {
+    previous: Option<u64>,
+}
+
+impl CorrelationGuard {
+    /// Tag the records of the current thread with `id`, use the
+    /// [`with_correlation!`](crate::with_correlation) macro instead of calling this directly
+    pub fn enter(id: u64) -> Self {
+        CorrelationGuard {
+            previous: CORRELATION_ID.with(|current| current.replace(Some(id))),
+        }
+    }
+}
+
+impl Drop for CorrelationGuard {
+    fn drop(&mut self) {
+        CORRELATION_ID.with(|current| current.set(self.previous));
+    }
+}
@@ -0,0 +1,134 @@
+use log::Record;
+
+/// Configuration of the `metrics` counters incremented for every record
+///
+/// Records are counted in a single counter labelled with the level (and optionally the target),
+/// such as `log_records_total{level="ERROR"}`.
+#[derive(Clone, Debug)]
+pub struct MetricsConfig {
+    /// Name of the counter (default is `log_records_total`)
+    pub counter_name: String,
+    /// Whether to add the record target as a `target` label (default is `false`)
+    pub target_label: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            counter_name: "log_records_total".to_string(),
+            target_label: false,
+        }
+    }
+}
+
+impl MetricsConfig {
+    /// Increment the counter for `record`
+    pub(crate) fn count(&self, record: &Record) {
+        let level = record.level().as_str();
+        if self.target_label {
+            metrics::counter!(self.counter_name.clone(), "level" => level, "target" => record.target().to_string()).increment(1);
+        } else {
+            metrics::counter!(self.counter_name.clone(), "level" => level).increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+    use metrics::{Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Counts by name and labels, such as `log_records_total{level=ERROR}`
+    type Counts = Arc<Mutex<HashMap<String, u64>>>;
+
+    #[derive(Default)]
+    struct TestRecorder {
+        counts: Counts,
+    }
+
+    struct TestCounter {
+        key: String,
+        counts: Counts,
+    }
+
+    impl CounterFn for TestCounter {
+        fn increment(&self, value: u64) {
+            *self.counts.lock().unwrap().entry(self.key.clone()).or_default() += value;
+        }
+
+        fn absolute(&self, value: u64) {
+            self.counts.lock().unwrap().insert(self.key.clone(), value);
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let labels = key.labels().map(|label| format!("{}={}", label.key(), label.value())).collect::<Vec<_>>();
+            Counter::from_arc(Arc::new(TestCounter {
+                key: format!("{}{{{}}}", key.name(), labels.join(",")),
+                counts: Arc::clone(&self.counts),
+            }))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    fn log(logger: &dyn Log, level: log::Level, target: &str) {
+        crate::__log_to(logger, level, target, file!(), line!(), format_args!("message\n"));
+    }
+
+    #[test]
+    fn every_written_record_increments_the_counter_of_its_level() {
+        let recorder = TestRecorder::default();
+        let path = std::env::temp_dir().join(format!("rimplog-metrics-{}.log", std::process::id()));
+        let logger = crate::NamedLogger::new(
+            "metrics",
+            crate::LoggerBuilder {
+                level: "info".to_string(),
+                level_source: crate::LevelSource::BuilderOnly,
+                file: Some(path.clone()),
+                ..crate::LoggerBuilder::default()
+            },
+        );
+        metrics::with_local_recorder(&recorder, || {
+            log(&logger, log::Level::Error, "rimplog");
+            log(&logger, log::Level::Error, "rimplog::db");
+            log(&logger, log::Level::Warn, "rimplog");
+            // Filtered out
+            log(&logger, log::Level::Debug, "rimplog");
+        });
+        let _ = std::fs::remove_file(path);
+
+        let counts = recorder.counts.lock().unwrap();
+        assert_eq!(counts.get("log_records_total{level=ERROR}"), Some(&2));
+        assert_eq!(counts.get("log_records_total{level=WARN}"), Some(&1));
+        assert_eq!(counts.get("log_records_total{level=DEBUG}"), None);
+    }
+
+    #[test]
+    fn counters_can_be_renamed_and_labelled_by_target() {
+        let recorder = TestRecorder::default();
+        let config = MetricsConfig {
+            counter_name: "app_logs".to_string(),
+            target_label: true,
+        };
+        metrics::with_local_recorder(&recorder, || {
+            config.count(&Record::builder().level(log::Level::Error).target("rimplog::db").build());
+        });
+        let counts = recorder.counts.lock().unwrap();
+        assert_eq!(counts.get("app_logs{level=ERROR,target=rimplog::db}"), Some(&1));
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::{DateTime, FixedOffset};
+use log::Record;
+use std::sync::mpsc::Sender;
+
+/// A record sent to `channel_sink`, for consumers that process records themselves such as a log viewer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEvent {
+    pub level: log::Level,
+    pub target: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    /// The message after redaction, without its trailing newline
+    pub message: String,
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+/// Send a record with its rendered message to `sender`, dropping it when the receiver is gone
+pub(crate) fn send(sender: &Sender<LogEvent>, record: &Record, message: &str, timestamp: DateTime<FixedOffset>) {
+    let _ = sender.send(LogEvent {
+        level: record.level(),
+        target: record.target().to_string(),
+        file: record.file().map(str::to_string),
+        line: record.line(),
+        message: message.strip_suffix('\n').unwrap_or(message).to_string(),
+        timestamp,
+    });
+}
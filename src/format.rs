@@ -0,0 +1,1147 @@
+use colored::*;
+use log::Record;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+use crate::{clock, correlation, span, ExternalDetail, LoggerBuilder, LoggerPreset, PathDepth, PathFallback};
+
+/// Level, target and message identifying a line for `collapse_consecutive`
+type LineKey = (log::Level, String, String);
+
+/// Formats records according to the logger configuration
+pub(crate) struct RecordFormatter {
+    config: LoggerBuilder,
+    project_name: String,
+    /// The previous line and how many times it has been repeated since
+    last_line: Mutex<Option<(LineKey, usize)>>,
+    /// The widest prefix seen so far, used to align messages
+    widest_prefix: AtomicUsize,
+    /// When the previous record was formatted, for `show_delta`
+    last_record: Mutex<Option<Instant>>,
+    /// The second of the previous timestamp, for `condense_timestamp`
+    last_second: AtomicI64,
+    /// The dimmed `build_id`, colored or not when it's written depending on the colors then
+    build_id: Option<ColoredString>,
+    /// Whether `hyperlink_paths` is set and the terminal supports hyperlinks
+    hyperlinks: bool,
+    #[cfg(feature = "tz")]
+    timezone: Option<chrono_tz::Tz>,
+    #[cfg(feature = "regex")]
+    target_regex: Option<regex::Regex>,
+}
+
+impl RecordFormatter {
+    pub(crate) fn new(config: LoggerBuilder, project_name: String) -> Self {
+        #[cfg(feature = "tz")]
+        let timezone = config.timezone.as_deref().and_then(|name| {
+            name.parse::<chrono_tz::Tz>()
+                .map_err(|_| eprintln!("Invalid time zone '{}', using local time", name))
+                .ok()
+        });
+        #[cfg(feature = "regex")]
+        let target_regex = config.target_regex.as_deref().and_then(|pattern| {
+            regex::Regex::new(pattern)
+                .map_err(|err| eprintln!("Invalid target regex '{}': {}, ignoring it", pattern, err))
+                .ok()
+        });
+
+        let build_id = config.build_id.as_deref().map(|id| id.dimmed());
+        let hyperlinks = config.hyperlink_paths && crate::console::hyperlinks_supported();
+
+        RecordFormatter {
+            config,
+            project_name,
+            last_line: Mutex::new(None),
+            widest_prefix: AtomicUsize::new(0),
+            last_record: Mutex::new(None),
+            last_second: AtomicI64::new(i64::MIN),
+            build_id,
+            hyperlinks,
+            #[cfg(feature = "tz")]
+            timezone,
+            #[cfg(feature = "regex")]
+            target_regex,
+        }
+    }
+
+    pub(crate) fn config(&self) -> &LoggerBuilder {
+        &self.config
+    }
+
+    /// Whether records are written as [`BinaryRecord`](crate::BinaryRecord)s instead of text
+    pub(crate) fn is_binary(&self) -> bool {
+        self.config.preset.is_binary()
+    }
+
+    /// Format the time of the record with `time_format`, the current time of the clock unless replayed
+    ///
+    /// With `condense_timestamp`, a record in the same second as the previous one gets spaces instead.
+    fn timestamp(&self) -> String {
+        let now = clock::record_time(&*self.config.clock);
+        let timestamp = self.format_time(now);
+        if self.config.condense_timestamp && self.last_second.swap(now.timestamp(), Ordering::Relaxed) == now.timestamp() {
+            return " ".repeat(timestamp.chars().count());
+        }
+        timestamp
+    }
+
+    /// Format `time` with `time_format`, in `timezone` if there is one
+    fn format_time(&self, time: chrono::DateTime<chrono::FixedOffset>) -> String {
+        #[cfg(feature = "tz")]
+        if let Some(timezone) = &self.timezone {
+            return time.with_timezone(timezone).format(&self.config.time_format).to_string();
+        }
+        time.format(&self.config.time_format).to_string()
+    }
+
+    /// Get the time since the previous record as `+123ms`, `+0ms` for the first one
+    fn delta(&self) -> String {
+        let now = Instant::now();
+        let previous = self.last_record.lock().unwrap_or_else(|e| e.into_inner()).replace(now);
+        let millis = previous.map(|previous| now.duration_since(previous).as_millis()).unwrap_or(0);
+        format!("+{}ms", millis)
+    }
+
+    /// Get the file and line of a record, only shown by the `FULL` preset
+    fn location(&self, record: &Record) -> String {
+        let file_path = record.file().unwrap_or("unknown");
+        let project_relative_path = self.relative_path(file_path);
+        let line = record.line().unwrap_or(0);
+
+        if self.is_project_record(record) {
+            let location = self.file_line(&project_relative_path, line);
+            self.hyperlink(file_path, line, location)
+        } else {
+            let target = match self.config.target_width {
+                Some(width) => fit_width(record.target(), width),
+                None => Cow::Borrowed(record.target()),
+            };
+            match self.config.external_detail {
+                ExternalDetail::TargetOnly => format!("{}",
+                    target.yellow()),
+                _ => {
+                    let location = self.file_line(&project_relative_path, line);
+                    format!("[{}] {}", target.yellow(), self.hyperlink(file_path, line, location))
+                }
+            }
+        }
+    }
+
+    /// Join a displayed path and a line as `path:line`, colored with `path_color` and `line_color`
+    fn file_line(&self, path: &str, line: u32) -> String {
+        let paint = |text: &str, color: Option<Color>| match color {
+            Some(color) => text.color(color).to_string(),
+            None => text.to_string(),
+        };
+        format!("{}:{}", paint(path, self.config.path_color), paint(&line.to_string(), self.config.line_color))
+    }
+
+    /// Wrap `text` in an OSC 8 hyperlink to `file_path` for `hyperlink_paths`, when the terminal shows them
+    fn hyperlink(&self, file_path: &str, line: u32, text: String) -> String {
+        if !self.hyperlinks || !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return text;
+        }
+        // Paths of the crate being built are relative to where cargo ran, usually the current directory
+        let path = match std::env::current_dir() {
+            Ok(dir) if Path::new(file_path).is_relative() => dir.join(file_path).display().to_string(),
+            _ => file_path.to_string(),
+        };
+        let url = self.config.hyperlink_url.replace("{path}", &path).replace("{line}", &line.to_string());
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    }
+
+    /// Get the displayed path of a record file
+    fn relative_path<'a>(&self, file_path: &'a str) -> Cow<'a, str> {
+        get_project_relative_path(file_path, self.config.path_depth, self.config.path_fallback)
+    }
+
+    /// Whether the record comes from the project rather than an external crate
+    fn is_project_record(&self, record: &Record) -> bool {
+        record.target().starts_with(&self.project_name)
+    }
+
+    /// Render the message of a record, with its key-values, redacted and sanitized
+    pub(crate) fn message(&self, record: &Record) -> String {
+        let mut message = record.args().to_string();
+        #[cfg(feature = "kv")]
+        append_key_values(&mut message, record, self.config.sort_kv);
+        if let Some(redactor) = &self.config.redactor {
+            if let Cow::Owned(redacted) = redactor(&message) {
+                message = redacted;
+            }
+        }
+        if self.config.sanitize_messages {
+            message = sanitize(&message);
+        }
+        // Last, so a secret cut short can't slip past the redactor
+        if let Some(truncated) = self.config.max_args_len.and_then(|max| truncate_balanced(&message, max)) {
+            message = truncated;
+        }
+        message
+    }
+
+    /// Whether records of `target` are logged at all, `ExternalDetail::Hidden` drops those of external
+    /// crates and `target_regex` those it doesn't match
+    ///
+    /// Checked by the logger before any output or counter sees a record.
+    pub(crate) fn logs_target(&self, target: &str) -> bool {
+        if self.config.external_detail == ExternalDetail::Hidden && !target.starts_with(&self.project_name) {
+            return false;
+        }
+        #[cfg(feature = "regex")]
+        if let Some(target_regex) = &self.target_regex {
+            return target_regex.is_match(target);
+        }
+        true
+    }
+
+    /// Write a record to `buf`, once [`logs_target`](Self::logs_target) passed it
+    pub(crate) fn write_record(&self, buf: &mut dyn Write, record: &Record) -> io::Result<()> {
+        let message = self.message(record);
+
+        #[cfg(feature = "binary")]
+        if self.is_binary() {
+            let mut bytes = Vec::new();
+            crate::BinaryRecord {
+                timestamp: clock::record_time(&*self.config.clock).to_utc(),
+                level: record.level(),
+                target: record.target().to_string(),
+                message: message.strip_suffix('\n').unwrap_or(&message).to_string(),
+            }
+            .encode(&mut bytes);
+            return buf.write_all(&bytes);
+        }
+
+        #[cfg(feature = "json")]
+        if self.config.preset.is_json() {
+            let mut line = self.json_object(record, &message).to_string();
+            line.push('\n');
+            return buf.write_all(line.as_bytes());
+        }
+
+        // Write the log message, but do not add a newline
+        match self.format(record, &message) {
+            Some(log_message) => write!(buf, "{}", log_message),
+            None => Ok(()),
+        }
+    }
+
+    /// Build the object of a record for the `JSON` preset, with its already rendered message
+    ///
+    /// The message loses the trailing newline added by the logging macros, while the newlines within
+    /// it are escaped by the serializer, so every record is one line however many lines it has.
+    #[cfg(feature = "json")]
+    fn json_object(&self, record: &Record, message: &str) -> serde_json::Value {
+        let mut object = serde_json::json!({
+            "timestamp": self.format_time(clock::record_time(&*self.config.clock)),
+            "level": level_label(record.level()),
+            "target": record.target(),
+            "thread": thread::current().name().unwrap_or("unknown"),
+            "file": record.file(),
+            "line": record.line(),
+            "message": message.strip_suffix('\n').unwrap_or(message),
+        });
+        if let Some(build_id) = &self.config.build_id {
+            object["build_id"] = serde_json::Value::from(build_id.as_str());
+        }
+        if let Some(id) = correlation::correlation_id() {
+            object["correlation_id"] = serde_json::Value::from(id);
+        }
+        object
+    }
+
+    /// Format a record with its already rendered message, returns `None` if the record is suppressed
+    pub(crate) fn format(&self, record: &Record, message: &str) -> Option<String> {
+        let config = &self.config;
+
+        let mut repeated_notice = String::new();
+        if config.collapse_consecutive {
+            let key = (record.level(), record.target().to_string(), message.to_string());
+            let mut last_line = self.last_line.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((last_key, repeats)) = last_line.as_mut() {
+                if *last_key == key {
+                    *repeats += 1;
+                    return None;
+                }
+                if *repeats > 0 {
+                    let times = if *repeats == 1 { "time" } else { "times" };
+                    repeated_notice = format!("(last line repeated {} {})\n", repeats, times);
+                }
+            }
+            *last_line = Some((key, 0));
+        }
+
+        let severity;
+        let label = if config.numeric_level {
+            severity = syslog_severity(record.level()).to_string();
+            &severity
+        } else if config.short_level {
+            &level_label(record.level())[..1]
+        } else {
+            level_label(record.level())
+        };
+        // The padding is kept out of the painted label and any brackets around it, and letters and
+        // severities are all one column wide
+        let label_width = if config.numeric_level || config.short_level { 1 } else { LEVEL_WIDTH };
+        let level_padding = if config.pad_level { label_width.saturating_sub(label.len()) } else { 0 };
+        let level_padding = " ".repeat(level_padding);
+        let mut level = config.color_theme.paint(record.level(), label);
+        if let Some(background) = config.level_bg_colors.as_ref().and_then(|colors| colors.get(&record.level())) {
+            level = level.on_color(*background);
+        }
+
+        let thread_name = thread::current().name().unwrap_or("unknown").to_string();
+        let is_main = thread_name == "main";
+        let mut thread_notice = String::new();
+        // `SIMPLE` and `RAW` don't show threads, so they don't need the notices either
+        let shows_thread = matches!(config.preset, LoggerPreset::FULL | LoggerPreset::THREAD);
+        let thread_name = if config.thread_name_map && shows_thread {
+            let (index, is_new) = thread_index(&thread_name);
+            if is_new {
+                thread_notice = format!("(thread #{} is {})\n", index, thread_name);
+            }
+            format!("#{}", index)
+        } else {
+            thread_name
+        };
+        #[cfg(feature = "tokio")]
+        let thread_name = match tokio::task::try_id().filter(|_| config.show_task_id) {
+            Some(id) => format!("{}#{}", thread_name, id),
+            None => thread_name,
+        };
+        let thread_colored = if is_main {
+            thread_name.bright_green()
+        } else {
+            thread_name.bright_blue()
+        };
+
+        #[cfg(feature = "json")]
+        let pretty_message = config.prettify_json_messages.then(|| prettify_json(message)).flatten();
+        #[cfg(feature = "json")]
+        let message = pretty_message.as_deref().unwrap_or(message);
+        let mut message = format!("{:indent$}{}", "", message, indent = span::span_depth() * 2);
+        if !config.highlight_rules.is_empty() {
+            message = highlight(&message, &config.highlight_rules, config.highlight_case_insensitive);
+        }
+
+        let mut timestamp = match config.timestamp_color {
+            Some(color) => self.timestamp().color(color).to_string(),
+            None => self.timestamp(),
+        };
+        if config.show_delta {
+            timestamp = format!("{} {}", timestamp, self.delta().dimmed());
+        }
+        if let Some(cpu_time) = config.show_cpu_time.then(clock::process_cpu_time).flatten() {
+            let cpu_time = format!("cpu={:.3}s", cpu_time.as_secs_f64());
+            timestamp = format!("{} {}", timestamp, cpu_time.dimmed());
+        }
+
+        // Everything before the message, including the separating space
+        let mut prefix = match config.preset {
+            LoggerPreset::FULL => {
+                format!(
+                    "{} {}{} [{}] [{}] ",
+                    timestamp,
+                    level,
+                    level_padding,
+                    thread_colored,
+                    self.location(record)
+                )
+            }
+            LoggerPreset::THREAD => {
+                format!(
+                    "{} {}{} [{}] ",
+                    timestamp,
+                    level,
+                    level_padding,
+                    thread_colored
+                )
+            }
+            LoggerPreset::SIMPLE => {
+                format!(
+                    "[ {} {}]{} ",
+                    timestamp,
+                    level,
+                    level_padding
+                )
+            }
+            LoggerPreset::RAW => String::new(),
+            #[cfg(feature = "binary")]
+            LoggerPreset::BINARY => unreachable!("binary records are not formatted as text"),
+            #[cfg(feature = "json")]
+            LoggerPreset::JSON => unreachable!("JSON records are not formatted as lines"),
+        };
+        if config.show_module_path {
+            let module_path = record.module_path().unwrap_or("unknown");
+            prefix.push_str(&format!("[{}] ", module_path.yellow()));
+        }
+        if let Some(build_id) = &self.build_id {
+            prefix.push_str(&format!("[{}] ", build_id));
+        }
+        if let Some(id) = correlation::correlation_id() {
+            prefix.push_str(&format!("[{}] ", format!("#{}", id).magenta()));
+        }
+
+        let width = visible_width(&prefix);
+        let padding = if config.align_message {
+            let column = if config.message_column > 0 {
+                config.message_column
+            } else {
+                self.widest_prefix.fetch_max(width, Ordering::Relaxed).max(width)
+            };
+            column.saturating_sub(width)
+        } else {
+            0
+        };
+
+        if config.multiline {
+            message = indent_continuation_lines(&message, width + padding);
+        }
+
+        let mut line = format!(
+            "{}{}{}{:padding$}{}",
+            repeated_notice,
+            thread_notice,
+            prefix,
+            "",
+            message,
+            padding = padding
+        );
+        // `RAW` is the message as it was logged, trailing spaces included
+        if config.trim_trailing && !matches!(config.preset, LoggerPreset::RAW) {
+            line = trim_trailing_whitespace(&line);
+        }
+        if config.line_prefix.is_some() || config.line_suffix.is_some() {
+            line = wrap_lines(
+                &line,
+                config.line_prefix.as_deref().unwrap_or(""),
+                config.line_suffix.as_deref().unwrap_or(""),
+            );
+        }
+        if config.ansi_reset && colored::control::SHOULD_COLORIZE.should_colorize() {
+            line = wrap_lines(&line, "", ANSI_RESET);
+            // The text after the last newline is continued by the next write, but mustn't bleed either
+            if !line.ends_with('\n') {
+                line.push_str(ANSI_RESET);
+            }
+        }
+        if config.progress_aware {
+            line.insert_str(0, crate::console::CLEAR_LINE);
+        }
+        Some(line)
+    }
+}
+
+/// Append the key-value pairs of a record to its message as `key=value`, before the trailing newline
+#[cfg(feature = "kv")]
+fn append_key_values(message: &mut String, record: &Record, sort: bool) {
+    struct Collect(Vec<(String, String)>);
+
+    impl<'kvs> log::kv::VisitSource<'kvs> for Collect {
+        fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    let mut pairs = Collect(Vec::new());
+    if record.key_values().visit(&mut pairs).is_err() || pairs.0.is_empty() {
+        return;
+    }
+    if sort {
+        // Stable, so repeated keys keep their order
+        pairs.0.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    let terminator = if message.ends_with('\n') {
+        message.pop();
+        "\n"
+    } else {
+        ""
+    };
+    for (key, value) in pairs.0 {
+        message.push_str(&format!(" {}={}", key, value));
+    }
+    message.push_str(terminator);
+}
+
+/// Indent a message that is a JSON object or array, keeping the trailing newline
+#[cfg(feature = "json")]
+fn prettify_json(message: &str) -> Option<String> {
+    let (body, terminator) = match message.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (message, ""),
+    };
+    let trimmed = body.trim();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return None;
+    }
+    let value: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+    let mut pretty = serde_json::to_string_pretty(&value).ok()?;
+    pretty.push_str(terminator);
+    Some(pretty)
+}
+
+/// Columns taken by the longest level name, the width names are padded to by `pad_level`
+const LEVEL_WIDTH: usize = 5;
+
+/// Get the label of a level, without padding
+fn level_label(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "ERROR",
+        log::Level::Warn => "WARN",
+        log::Level::Info => "INFO",
+        log::Level::Debug => "DEBUG",
+        log::Level::Trace => "TRACE",
+    }
+}
+
+/// Pad `text` with spaces to `width` characters, or cut it to `width` ending with `…`
+fn fit_width(text: &str, width: usize) -> Cow<'_, str> {
+    let length = text.chars().count();
+    if length == width {
+        Cow::Borrowed(text)
+    } else if length < width {
+        Cow::Owned(format!("{:width$}", text, width = width))
+    } else {
+        let mut cut = text.chars().take(width.saturating_sub(1)).collect::<String>();
+        if width > 0 {
+            cut.push('…');
+        }
+        Cow::Owned(cut)
+    }
+}
+
+/// Cut `message` to `max` characters for `max_args_len`, or `None` if it is short enough
+///
+/// The brackets left open by the cut are closed after the `…`, ignoring those in string
+/// literals, so a cut `{:#?}` output still reads as nested values. The trailing newline is kept.
+fn truncate_balanced(message: &str, max: usize) -> Option<String> {
+    let (text, newline) = match message.strip_suffix('\n') {
+        Some(text) => (text, "\n"),
+        None => (message, ""),
+    };
+    let length = text.chars().count();
+    if length <= max {
+        return None;
+    }
+
+    let cut: String = text.chars().take(max).collect();
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in cut.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' => open.push(')'),
+            '[' => open.push(']'),
+            '{' => open.push('}'),
+            ')' | ']' | '}' => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let mut truncated = cut;
+    truncated.push('…');
+    if in_string {
+        truncated.push('"');
+    }
+    for closing in open.iter().rev() {
+        // Braces of structs are padded with spaces, as in `Point { x: 1 }`
+        if *closing == '}' {
+            truncated.push(' ');
+        }
+        truncated.push(*closing);
+    }
+    Some(format!("{} ({} chars){}", truncated, length, newline))
+}
+
+/// Indexes of the thread names for `thread_name_map`, shared by every formatter so they stay stable
+static THREAD_INDEXES: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+
+/// Get the index of a thread name starting at 1, and whether it was assigned by this call
+fn thread_index(name: &str) -> (usize, bool) {
+    let mut indexes = THREAD_INDEXES
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(&index) = indexes.get(name) {
+        return (index, false);
+    }
+    let index = indexes.len() + 1;
+    indexes.insert(name.to_string(), index);
+    (index, true)
+}
+
+/// Get the syslog severity of a level, trace has no severity of its own and shares debug's
+pub(crate) fn syslog_severity(level: log::Level) -> u8 {
+    match level {
+        log::Level::Error => 3,
+        log::Level::Warn => 4,
+        log::Level::Info => 6,
+        log::Level::Debug | log::Level::Trace => 7,
+    }
+}
+
+/// Escape resetting all colors and styles, ending every line with `ansi_reset`
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Marker starting the continuation lines of a multiline message
+const CONTINUATION_MARKER: &str = "| ";
+
+/// Indent the lines after the first so they start at `column`, behind a continuation marker
+///
+/// The trailing newline added by the logging macros doesn't start a continuation line.
+fn indent_continuation_lines(message: &str, column: usize) -> String {
+    let (body, terminator) = match message.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (message, ""),
+    };
+    if !body.contains('\n') {
+        return message.to_string();
+    }
+    let indent = column.saturating_sub(CONTINUATION_MARKER.len());
+    let separator = format!("\n{:indent$}{}", "", CONTINUATION_MARKER, indent = indent);
+    let mut indented = body.split('\n').collect::<Vec<_>>().join(&separator);
+    indented.push_str(terminator);
+    indented
+}
+
+/// Put `prefix` at the start and `suffix` at the end of every line
+///
+/// Text after the last newline only gets the prefix, since the line is continued by the next write.
+fn wrap_lines(s: &str, prefix: &str, suffix: &str) -> String {
+    let mut wrapped = String::with_capacity(s.len() + prefix.len() + suffix.len());
+    let mut lines = s.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_some() {
+            wrapped.push_str(prefix);
+            wrapped.push_str(line);
+            wrapped.push_str(suffix);
+            wrapped.push('\n');
+        } else if !line.is_empty() {
+            wrapped.push_str(prefix);
+            wrapped.push_str(line);
+        }
+    }
+    wrapped
+}
+
+/// Remove the whitespace before every newline
+///
+/// Text after the last newline is kept as is, since the line is continued by the next write.
+fn trim_trailing_whitespace(s: &str) -> String {
+    let mut trimmed = String::with_capacity(s.len());
+    let mut lines = s.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if lines.peek().is_some() {
+            trimmed.push_str(line.trim_end());
+            trimmed.push('\n');
+        } else {
+            trimmed.push_str(line);
+        }
+    }
+    trimmed
+}
+
+/// Escape newlines, carriage returns and ANSI escapes so untrusted input can't forge lines
+///
+/// The trailing newline added by the logging macros is kept.
+fn sanitize(message: &str) -> String {
+    let (body, terminator) = match message.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (message, ""),
+    };
+    let mut sanitized = String::with_capacity(message.len());
+    for c in body.chars() {
+        match c {
+            '\n' => sanitized.push_str("\\n"),
+            '\r' => sanitized.push_str("\\r"),
+            '\x1b' => sanitized.push_str("\\x1b"),
+            c => sanitized.push(c),
+        }
+    }
+    sanitized.push_str(terminator);
+    sanitized
+}
+
+/// Get the display width of a string, ignoring ANSI escape sequences
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Skip the rest of an escape sequence after its `\x1b`
+///
+/// Color codes end with their final letter, while OSC strings such as hyperlinks end
+/// with BEL or `\x1b\\`.
+fn skip_escape(chars: &mut std::str::Chars) {
+    if chars.as_str().starts_with(']') {
+        while let Some(c) = chars.next() {
+            if c == '\x07' {
+                break;
+            }
+            if c == '\x1b' {
+                chars.next();
+                break;
+            }
+        }
+    } else {
+        for c in chars.by_ref() {
+            if c.is_ascii_alphabetic() {
+                break;
+            }
+        }
+    }
+}
+
+/// Remove ANSI escape sequences, for sinks that env_logger doesn't strip them for
+pub(crate) fn strip_ansi(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Color the substrings of `message` matched by `rules`
+///
+/// Rules are tried in order at each position, the first match wins.
+fn highlight(message: &str, rules: &[(String, Color)], case_insensitive: bool) -> String {
+    let mut highlighted = String::with_capacity(message.len());
+    let mut rest = message;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (pattern, color) in rules {
+            let matched = !pattern.is_empty()
+                && rest.len() >= pattern.len()
+                && rest.is_char_boundary(pattern.len())
+                && if case_insensitive {
+                    rest[..pattern.len()].eq_ignore_ascii_case(pattern)
+                } else {
+                    rest.starts_with(pattern.as_str())
+                };
+            if matched {
+                highlighted.push_str(&rest[..pattern.len()].color(*color).to_string());
+                rest = &rest[pattern.len()..];
+                continue 'outer;
+            }
+        }
+        highlighted.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    highlighted
+}
+
+/// Get the project relative path
+///
+/// Plain paths with single `/` separators, as `file!()` mostly gives, are sliced so formatting a
+/// record neither allocates nor needs a cache for them. Others are normalized in a new string.
+pub(crate) fn get_project_relative_path(file_path: &str, depth: PathDepth, fallback: PathFallback) -> Cow<'_, str> {
+    // The root of absolute paths would be an empty leading component
+    let trimmed = file_path.trim_start_matches('/');
+
+    // One pass over the bytes finding the components, as this runs for every record
+    let mut total = 0;
+    let mut src = None;
+    // Count the component from `start` to `end`, returning whether it needs no normalizing
+    let mut component = |start: usize, end: usize| {
+        let name = &trimmed[start..end];
+        if src.is_none() && name == "src" {
+            src = Some((total, start));
+        }
+        total += 1;
+        !name.is_empty() && name != "."
+    };
+    let mut start = 0;
+    for (index, byte) in trimmed.bytes().enumerate() {
+        let plain = match byte {
+            b'/' => {
+                let plain = component(start, index);
+                start = index + 1;
+                plain
+            }
+            b'\\' => false,
+            _ => true,
+        };
+        if !plain {
+            return Cow::Owned(normalized_relative_path(file_path, depth, fallback));
+        }
+    }
+    if !component(start, trimmed.len()) {
+        return Cow::Owned(normalized_relative_path(file_path, depth, fallback));
+    }
+    if src.is_none() && fallback == PathFallback::AsIs {
+        return Cow::Borrowed(file_path);
+    }
+
+    let first = relevant_start(src.map(|(index, _)| index), total, depth, fallback);
+    let offset = match src {
+        Some((index, offset)) if index == first => offset,
+        // Count the kept components back from the end
+        _ => (first..total).fold(trimmed.len() + 1, |end, _| trimmed[..end - 1].rfind('/').map_or(0, |index| index + 1)),
+    };
+    Cow::Borrowed(&trimmed[offset.min(trimmed.len())..])
+}
+
+/// Get the index of the first displayed component out of `total`, given the index of `src`
+fn relevant_start(src: Option<usize>, total: usize, depth: PathDepth, fallback: PathFallback) -> usize {
+    // Start at the "src" directory
+    let first = match src {
+        Some(index) => index,
+        None => match fallback {
+            // `AsIs` paths are returned unchanged before
+            PathFallback::FullPath | PathFallback::AsIs => 0,
+            PathFallback::FileNameOnly => total.saturating_sub(1),
+        },
+    };
+    // Keep the last `depth` components, or the full path if there are not that many. Saturating,
+    // so depths past the number of components such as `usize::MAX` can't underflow
+    match depth {
+        PathDepth::Full => first,
+        PathDepth::Components(depth) => first.max(total.saturating_sub(depth)),
+        PathDepth::FileOnly => first.max(total.saturating_sub(1)),
+    }
+}
+
+/// Get the project relative path of a path whose separators must be normalized, joining its components with `/`
+fn normalized_relative_path(file_path: &str, depth: PathDepth, fallback: PathFallback) -> String {
+    let components: Vec<&str> = Path::new(file_path)
+        .components()
+        .filter(|c| !matches!(c, Component::RootDir))
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect();
+    let src = components.iter().position(|&c| c == "src");
+    if src.is_none() && fallback == PathFallback::AsIs {
+        return file_path.to_string();
+    }
+    let first = relevant_start(src, components.len(), depth, fallback);
+    components[first..].join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `f` with a record of `target` at `level` logged from `src/db/pool.rs:42`
+    fn with_record<T>(level: log::Level, target: &str, message: &str, f: impl FnOnce(&Record) -> T) -> T {
+        f(&Record::builder()
+            .args(format_args!("{}\n", message))
+            .level(level)
+            .target(target)
+            .module_path(Some(target))
+            .file(Some("src/db/pool.rs"))
+            .line(Some(42))
+            .build())
+    }
+
+    /// A `SIMPLE` configuration with a clock stopped at `2024-10-18 09:30:00`
+    fn simple() -> LoggerBuilder {
+        let time = chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap();
+        LoggerBuilder {
+            preset: LoggerPreset::SIMPLE,
+            ..LoggerBuilder::default()
+        }
+        .clock(crate::testing::FixedClock::new(time))
+    }
+
+    /// Format info records with the messages one after the other with the same formatter, without colors
+    fn lines(config: LoggerBuilder, messages: &[&str]) -> Vec<Option<String>> {
+        let formatter = RecordFormatter::new(config, "rimplog".to_string());
+        messages
+            .iter()
+            .map(|message| {
+                with_record(log::Level::Info, "rimplog", message, |record| {
+                    formatter.format(record, &formatter.message(record)).map(|line| strip_ansi(&line))
+                })
+            })
+            .collect()
+    }
+
+    /// Format a record with colors, as written to a terminal
+    fn colored_line(config: LoggerBuilder, level: log::Level, target: &str, message: &str) -> String {
+        let _colors = crate::console::force_colors();
+        let formatter = RecordFormatter::new(config, "rimplog".to_string());
+        with_record(level, target, message, |record| formatter.format(record, &formatter.message(record)).unwrap())
+    }
+
+    const REGISTRY_PATH: &str = "/home/user/.cargo/registry/src/index.crates.io-6f17d22bba15001f/hyper-1.0.0/proto/h1/conn.rs";
+
+    #[test]
+    fn path_depth_variants_select_the_components() {
+        let path = "/home/user/app/src/db/pool.rs";
+        let relative = |depth| get_project_relative_path(path, depth, PathFallback::FullPath);
+        assert_eq!(relative(PathDepth::Full), "src/db/pool.rs");
+        assert_eq!(relative(PathDepth::Components(2)), "db/pool.rs");
+        assert_eq!(relative(PathDepth::FileOnly), "pool.rs");
+        assert_eq!(relative(PathDepth::Components(0)), "");
+        assert_eq!(relative(PathDepth::Components(usize::MAX)), "src/db/pool.rs");
+
+        // Depths from before `PathDepth` keep their meaning
+        assert_eq!(PathDepth::from(0), PathDepth::Full);
+        assert_eq!(PathDepth::from(2), PathDepth::Components(2));
+    }
+
+    #[test]
+    fn path_depth_around_the_number_of_components() {
+        // `src`, `db` and `pool.rs`
+        let path = "/home/user/app/src/db/pool.rs";
+        let relative = |depth| get_project_relative_path(path, PathDepth::Components(depth), PathFallback::FullPath);
+        assert_eq!(relative(3), "src/db/pool.rs");
+        assert_eq!(relative(2), "db/pool.rs");
+        assert_eq!(relative(4), "src/db/pool.rs");
+        assert_eq!(relative(0), "");
+        assert_eq!(get_project_relative_path(path, PathDepth::from(0), PathFallback::FullPath), "src/db/pool.rs");
+        // A path without components
+        assert_eq!(get_project_relative_path("", PathDepth::Components(usize::MAX), PathFallback::FullPath), "");
+        assert_eq!(get_project_relative_path("", PathDepth::FileOnly, PathFallback::FullPath), "");
+    }
+
+    #[test]
+    fn project_relative_path_borrows_the_end_of_the_path() {
+        let path = "/home/user/app/src/db/pool.rs";
+        let relative = get_project_relative_path(path, PathDepth::Full, PathFallback::FullPath);
+        assert!(matches!(relative, Cow::Borrowed("src/db/pool.rs")), "{:?}", relative);
+        let relative = get_project_relative_path("/home/user/.cargo/proto/conn.rs", PathDepth::Full, PathFallback::FileNameOnly);
+        assert!(matches!(relative, Cow::Borrowed("conn.rs")), "{:?}", relative);
+        // Separators that aren't a single `/` are normalized in a new string
+        let relative = get_project_relative_path("/home/user/app/src//db/./pool.rs", PathDepth::Full, PathFallback::FullPath);
+        assert!(matches!(relative, Cow::Owned(_)), "{:?}", relative);
+        assert_eq!(relative, "src/db/pool.rs");
+    }
+
+    #[test]
+    fn path_fallback_full_path_keeps_every_component() {
+        assert_eq!(
+            get_project_relative_path("/home/user/.cargo/git/checkouts/dep/proto/conn.rs", PathDepth::Full, PathFallback::FullPath),
+            "home/user/.cargo/git/checkouts/dep/proto/conn.rs"
+        );
+        assert_eq!(
+            get_project_relative_path("/home/user/.cargo/git/checkouts/dep/proto/conn.rs", PathDepth::Components(2), PathFallback::FullPath),
+            "proto/conn.rs"
+        );
+    }
+
+    #[test]
+    fn path_fallback_file_name_only_hides_the_directories() {
+        assert_eq!(
+            get_project_relative_path("/home/user/.cargo/git/checkouts/dep/proto/conn.rs", PathDepth::Full, PathFallback::FileNameOnly),
+            "conn.rs"
+        );
+    }
+
+    #[test]
+    fn path_fallback_as_is_prints_the_path_unchanged() {
+        assert_eq!(
+            get_project_relative_path("/home/user/.cargo/git/checkouts/dep/proto/conn.rs", PathDepth::Components(1), PathFallback::AsIs),
+            "/home/user/.cargo/git/checkouts/dep/proto/conn.rs"
+        );
+    }
+
+    #[test]
+    fn path_fallback_is_not_used_for_paths_with_src() {
+        for fallback in [PathFallback::FullPath, PathFallback::FileNameOnly, PathFallback::AsIs] {
+            assert_eq!(
+                get_project_relative_path(REGISTRY_PATH, PathDepth::Full, fallback),
+                "src/index.crates.io-6f17d22bba15001f/hyper-1.0.0/proto/h1/conn.rs"
+            );
+        }
+    }
+
+    #[test]
+    fn highlight_rules_color_the_matched_words() {
+        let config = LoggerBuilder {
+            highlight_rules: vec![("FAILED".to_string(), Color::Red), ("ok".to_string(), Color::Green)],
+            ..LoggerBuilder::default()
+        };
+        let line = colored_line(config, log::Level::Info, "rimplog", "3 ok, 1 FAILED, 1 failed");
+        assert!(line.contains("\x1b[32mok\x1b[0m, 1 \x1b[31mFAILED\x1b[0m, 1 failed"), "{:?}", line);
+    }
+
+    #[test]
+    fn highlight_rules_can_ignore_case() {
+        let config = LoggerBuilder {
+            highlight_rules: vec![("FAILED".to_string(), Color::Red)],
+            highlight_case_insensitive: true,
+            ..LoggerBuilder::default()
+        };
+        let line = colored_line(config, log::Level::Info, "rimplog", "1 failed");
+        assert!(line.contains("1 \x1b[31mfailed\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn short_level_is_a_colored_letter() {
+        let config = LoggerBuilder {
+            short_level: true,
+            ..simple()
+        };
+        let line = colored_line(config.clone(), log::Level::Error, "rimplog", "boom");
+        assert!(line.contains("\x1b[1;31mE\x1b[0m"), "{:?}", line);
+        let labels: Vec<String> = [log::Level::Warn, log::Level::Info, log::Level::Debug, log::Level::Trace]
+            .into_iter()
+            .map(|level| {
+                let line = with_record(level, "rimplog", "boom", |record| {
+                    let formatter = RecordFormatter::new(config.clone(), "rimplog".to_string());
+                    strip_ansi(&formatter.format(record, &formatter.message(record)).unwrap())
+                });
+                line["[ 2024-10-18 09:30:00 ".len()..].chars().next().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(labels, ["W", "I", "D", "T"]);
+    }
+
+    #[test]
+    fn ansi_reset_ends_every_colored_line() {
+        let message = "user \x1b[31madmin\nsecond line";
+        let line = colored_line(simple(), log::Level::Info, "rimplog", message);
+        let lines: Vec<&str> = line.split_inclusive('\n').collect();
+        assert_eq!(lines.len(), 2, "{:?}", line);
+        for line in lines {
+            assert!(line.ends_with("\x1b[0m\n"), "{:?}", line);
+        }
+
+        let config = LoggerBuilder {
+            ansi_reset: false,
+            ..simple()
+        };
+        let line = colored_line(config, log::Level::Info, "rimplog", message);
+        assert!(line.ends_with("\nsecond line\n"), "{:?}", line);
+    }
+
+    #[test]
+    fn path_and_line_colors_are_separate() {
+        let config = LoggerBuilder {
+            path_color: Some(Color::Blue),
+            line_color: Some(Color::Red),
+            ..simple().preset(LoggerPreset::FULL)
+        };
+        let line = colored_line(config.clone(), log::Level::Info, "rimplog::db", "ready");
+        assert!(line.contains("[\x1b[34msrc/db/pool.rs\x1b[0m:\x1b[31m42\x1b[0m]"), "{:?}", line);
+        // External records too
+        let line = colored_line(config, log::Level::Info, "hyper", "ready");
+        assert!(line.contains("\x1b[34msrc/db/pool.rs\x1b[0m:\x1b[31m42\x1b[0m]"), "{:?}", line);
+
+        // The path keeps its default yellow
+        let config = LoggerBuilder {
+            line_color: Some(Color::Red),
+            ..simple().preset(LoggerPreset::FULL)
+        };
+        let line = colored_line(config, log::Level::Info, "rimplog::db", "ready");
+        assert!(line.contains("[\x1b[33msrc/db/pool.rs\x1b[0m:\x1b[31m42\x1b[0m]"), "{:?}", line);
+    }
+
+    #[test]
+    fn level_bg_colors_add_a_background_to_the_label() {
+        let config = LoggerBuilder {
+            level_bg_colors: Some(HashMap::from([(log::Level::Error, Color::White)])),
+            ..simple()
+        };
+        let line = colored_line(config.clone(), log::Level::Error, "rimplog", "boom");
+        assert!(line.contains("\x1b[1;47;31mERROR\x1b[0m"), "{:?}", line);
+        let line = colored_line(config, log::Level::Warn, "rimplog", "careful");
+        assert!(line.contains("\x1b[1;33mWARN\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn timestamp_color_colors_the_timestamp_of_every_preset() {
+        for preset in [LoggerPreset::FULL, LoggerPreset::THREAD, LoggerPreset::SIMPLE] {
+            let config = LoggerBuilder {
+                timestamp_color: Some(Color::Magenta),
+                ..simple().preset(preset)
+            };
+            let line = colored_line(config, log::Level::Info, "rimplog", "ready");
+            assert!(line.contains("\x1b[35m2024-10-18 09:30:00\x1b[0m"), "{:?}", line);
+        }
+        let config = LoggerBuilder {
+            timestamp_color: None,
+            ..simple()
+        };
+        let line = colored_line(config, log::Level::Info, "rimplog", "ready");
+        assert!(line.starts_with("[ 2024-10-18 09:30:00 "), "{:?}", line);
+    }
+
+    #[test]
+    fn numeric_level_is_colored_like_the_label() {
+        let config = LoggerBuilder {
+            numeric_level: true,
+            ..simple()
+        };
+        let line = colored_line(config, log::Level::Error, "rimplog", "boom");
+        assert!(line.contains("\x1b[1;31m3\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn collapse_consecutive_only_collapses_immediate_repeats() {
+        let config = LoggerBuilder {
+            collapse_consecutive: true,
+            ..simple()
+        };
+        let lines = lines(config, &["a", "a", "a", "b", "a", "b", "b", "a"]);
+        let line = |message: &str| Some(format!("[ 2024-10-18 09:30:00 INFO]  {}\n", message));
+        assert_eq!(
+            lines,
+            [
+                line("a"),
+                None,
+                None,
+                Some(format!("(last line repeated 2 times)\n{}", line("b").unwrap())),
+                line("a"),
+                line("b"),
+                None,
+                Some(format!("(last line repeated 1 time)\n{}", line("a").unwrap())),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_message_starts_messages_at_the_same_column() {
+        let full = || LoggerBuilder {
+            align_message: true,
+            ..simple()
+        }
+        .preset(LoggerPreset::FULL);
+        let column = |formatter: &RecordFormatter, level, target: &str| {
+            let line = with_record(level, target, "payload", |record| formatter.format(record, &formatter.message(record)).unwrap());
+            strip_ansi(&line).find("payload").unwrap()
+        };
+
+        // The widest prefix so far
+        let formatter = RecordFormatter::new(full(), "rimplog".to_string());
+        let external = column(&formatter, log::Level::Error, "hyper::proto::h1");
+        let project = column(&formatter, log::Level::Info, "rimplog");
+        assert_eq!(project, external);
+
+        let formatter = RecordFormatter::new(LoggerBuilder { message_column: 160, ..full() }, "rimplog".to_string());
+        assert_eq!(column(&formatter, log::Level::Info, "rimplog"), 160);
+        assert_eq!(column(&formatter, log::Level::Warn, "hyper"), 160);
+    }
+}
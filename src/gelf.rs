@@ -0,0 +1,225 @@
+use chrono::{DateTime, FixedOffset};
+use log::Record;
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::format::syslog_severity;
+use crate::network::NetworkWriter;
+use crate::NetworkSink;
+
+/// Largest datagram sent to Graylog, larger messages are chunked
+const MAX_CHUNK_SIZE: usize = 8192;
+/// Magic bytes, message id, sequence number and sequence count starting every chunk
+const CHUNK_HEADER_SIZE: usize = 12;
+/// Most chunks Graylog accepts for one message
+const MAX_CHUNKS: usize = 128;
+
+static NEXT_MESSAGE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Sends every record to Graylog as a GELF message, see `gelf`
+pub(crate) struct GelfWriter {
+    transport: Arc<NetworkWriter>,
+    udp: bool,
+    host: String,
+}
+
+impl GelfWriter {
+    pub(crate) fn new(sink: NetworkSink) -> Self {
+        GelfWriter {
+            udp: matches!(sink, NetworkSink::Udp(_)),
+            transport: NetworkWriter::new(sink, None),
+            host: hostname(),
+        }
+    }
+
+    /// Send `record` with its already rendered message
+    pub(crate) fn write(&self, record: &Record, message: &str, timestamp: DateTime<FixedOffset>) {
+        let payload = gelf_message(record, message, &self.host, timestamp).to_string().into_bytes();
+        if !self.udp {
+            // TCP messages are delimited by a null byte
+            let mut payload = payload;
+            payload.push(0);
+            self.transport.write(&payload);
+        } else if payload.len() <= MAX_CHUNK_SIZE {
+            self.transport.write(&payload);
+        } else {
+            let chunks = payload.chunks(MAX_CHUNK_SIZE - CHUNK_HEADER_SIZE).collect::<Vec<_>>();
+            if chunks.len() > MAX_CHUNKS {
+                return;
+            }
+            let message_id = message_id();
+            for (sequence, chunk) in chunks.iter().enumerate() {
+                let mut datagram = Vec::with_capacity(CHUNK_HEADER_SIZE + chunk.len());
+                datagram.extend_from_slice(&[0x1e, 0x0f]);
+                datagram.extend_from_slice(&message_id.to_be_bytes());
+                datagram.push(sequence as u8);
+                datagram.push(chunks.len() as u8);
+                datagram.extend_from_slice(chunk);
+                self.transport.write(&datagram);
+            }
+        }
+    }
+}
+
+/// Build the GELF 1.1 message of `record`, its location and target are additional fields
+///
+/// A multi-line message stays one JSON object with escaped newlines: its first line is the
+/// `short_message`, and the whole message the `full_message`.
+fn gelf_message(record: &Record, message: &str, host: &str, timestamp: DateTime<FixedOffset>) -> Value {
+    let message = message.strip_suffix('\n').unwrap_or(message);
+    let short_message = message.lines().next().unwrap_or("");
+    let mut gelf = json!({
+        "version": "1.1",
+        "host": host,
+        "short_message": short_message,
+        "timestamp": timestamp.timestamp_millis() as f64 / 1000.0,
+        "level": syslog_severity(record.level()),
+        "_target": record.target(),
+    });
+    if short_message.len() < message.len() {
+        gelf["full_message"] = Value::from(message);
+    }
+    if let Some(file) = record.file() {
+        gelf["_file"] = Value::from(file);
+    }
+    if let Some(line) = record.line() {
+        gelf["_line"] = Value::from(line);
+    }
+    gelf
+}
+
+/// Get an id telling the chunks of different messages apart
+fn message_id() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    nanos ^ NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed).rotate_left(32)
+}
+
+/// Get the host name reported in the `host` field
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::{TcpListener, UdpSocket};
+    use std::time::Duration;
+
+    use super::*;
+
+    fn timestamp() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2024-10-18T09:30:00.250+08:00").unwrap()
+    }
+
+    /// Run `f` with a record of `level` logged from `src/db/pool.rs:42`
+    fn with_record<T>(level: log::Level, f: impl FnOnce(&Record) -> T) -> T {
+        f(&Record::builder()
+            .args(format_args!("pool exhausted\n"))
+            .level(level)
+            .target("my_app::db")
+            .file(Some("src/db/pool.rs"))
+            .line(Some(42))
+            .build())
+    }
+
+    #[test]
+    fn messages_have_the_gelf_keys() {
+        let gelf = with_record(log::Level::Error, |record| gelf_message(record, "pool exhausted\n", "db-1", timestamp()));
+        assert_eq!(
+            gelf,
+            json!({
+                "version": "1.1",
+                "host": "db-1",
+                "short_message": "pool exhausted",
+                "timestamp": 1729215000.25,
+                "level": 3,
+                "_target": "my_app::db",
+                "_file": "src/db/pool.rs",
+                "_line": 42,
+            })
+        );
+    }
+
+    #[test]
+    fn levels_are_syslog_severities() {
+        let levels = [
+            (log::Level::Error, 3),
+            (log::Level::Warn, 4),
+            (log::Level::Info, 6),
+            (log::Level::Debug, 7),
+            (log::Level::Trace, 7),
+        ];
+        for (level, severity) in levels {
+            let gelf = with_record(level, |record| gelf_message(record, "", "db-1", timestamp()));
+            assert_eq!(gelf["level"], severity, "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn udp_messages_are_one_datagram_or_chunks() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let writer = GelfWriter::new(NetworkSink::Udp(socket.local_addr().unwrap()));
+        let mut buf = vec![0; MAX_CHUNK_SIZE];
+
+        with_record(log::Level::Warn, |record| writer.write(record, "slow query\n", timestamp()));
+        let len = socket.recv(&mut buf).unwrap();
+        let gelf: Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(gelf["short_message"], "slow query");
+        assert_eq!(gelf["level"], 4);
+
+        let large = "x".repeat(MAX_CHUNK_SIZE * 2);
+        with_record(log::Level::Warn, |record| writer.write(record, &large, timestamp()));
+        let mut payload = Vec::new();
+        let mut message_id = None;
+        for sequence in 0..3 {
+            let len = socket.recv(&mut buf).unwrap();
+            assert_eq!(buf[..2], [0x1e, 0x0f]);
+            assert_eq!(*message_id.get_or_insert(buf[2..10].to_vec()), buf[2..10]);
+            assert_eq!(buf[10..12], [sequence, 3]);
+            payload.extend_from_slice(&buf[12..len]);
+        }
+        let gelf: Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(gelf["short_message"], large.as_str());
+    }
+
+    #[test]
+    fn tcp_messages_end_with_a_null_byte() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let writer = GelfWriter::new(NetworkSink::Tcp(listener.local_addr().unwrap()));
+        with_record(log::Level::Info, |record| writer.write(record, "ready\n", timestamp()));
+        drop(writer);
+        let mut received = Vec::new();
+        listener.accept().unwrap().0.read_to_end(&mut received).unwrap();
+        assert_eq!(received.pop(), Some(0));
+        let gelf: Value = serde_json::from_slice(&received).unwrap();
+        assert_eq!(gelf["short_message"], "ready");
+    }
+
+    #[test]
+    fn multi_line_messages_are_one_object() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let writer = GelfWriter::new(NetworkSink::Tcp(listener.local_addr().unwrap()));
+        let message = "query failed\n  at pool.rs:42\n  at main.rs:7\n";
+        with_record(log::Level::Error, |record| writer.write(record, message, timestamp()));
+        drop(writer);
+        let mut received = Vec::new();
+        listener.accept().unwrap().0.read_to_end(&mut received).unwrap();
+        assert_eq!(received.pop(), Some(0));
+        // The newlines are escaped inside the strings
+        assert!(!received.contains(&b'\n'));
+        let gelf: Value = serde_json::from_slice(&received).unwrap();
+        assert_eq!(gelf["short_message"], "query failed");
+        assert_eq!(gelf["full_message"], "query failed\n  at pool.rs:42\n  at main.rs:7");
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::Write;
+
+/// Number of bytes dumped by [`log_hexdump!`](crate::log_hexdump) unless another cap is given
+pub const HEXDUMP_MAX_BYTES: usize = 4096;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Render `bytes` as a classic offset, hex and ASCII dump, used by [`log_hexdump!`](crate::log_hexdump)
+///
+/// The first line is the length, followed by one line per 16 bytes. Bytes past `max_bytes`
+/// are left out and counted on a last line.
+#[doc(hidden)]
+pub fn __hexdump(bytes: &[u8], max_bytes: usize) -> String {
+    let shown = &bytes[..bytes.len().min(max_bytes)];
+    let mut dump = format!("{} bytes", bytes.len());
+    for (index, chunk) in shown.chunks(BYTES_PER_LINE).enumerate() {
+        let _ = write!(dump, "\n{:08x} ", index * BYTES_PER_LINE);
+        for column in 0..BYTES_PER_LINE {
+            // An extra space between the two halves of the line
+            if column % 8 == 0 {
+                dump.push(' ');
+            }
+            match chunk.get(column) {
+                Some(byte) => {
+                    let _ = write!(dump, "{:02x} ", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str(" |");
+        dump.extend(chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }));
+        dump.push('|');
+    }
+    if shown.len() < bytes.len() {
+        let _ = write!(dump, "\n... {} more bytes", bytes.len() - shown.len());
+    }
+    dump.push('\n');
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexdump_shows_offsets_hex_and_ascii() {
+        let bytes = b"GET / HTTP/1.1\r\n\x00\x01\xff!";
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(
+            __hexdump(bytes, HEXDUMP_MAX_BYTES),
+            concat!(
+                "20 bytes\n",
+                "00000000  47 45 54 20 2f 20 48 54  54 50 2f 31 2e 31 0d 0a  |GET / HTTP/1.1..|\n",
+                "00000010  00 01 ff 21                                       |...!|\n",
+            )
+        );
+    }
+
+    #[test]
+    fn hexdump_cuts_large_buffers() {
+        let dump = __hexdump(&[0xab; 40], 16);
+        assert_eq!(
+            dump,
+            concat!(
+                "40 bytes\n",
+                "00000000  ab ab ab ab ab ab ab ab  ab ab ab ab ab ab ab ab  |................|\n",
+                "... 24 more bytes\n",
+            )
+        );
+    }
+}
@@ -1,130 +1,814 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 pub extern crate log;
-use env_logger::{Builder, Env};
-use std::io::Write;
-use chrono::Local;
-use colored::*;
-use std::path::Path;
-use std::thread;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::process;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
 use std::env;
-use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex, OnceLock};
+#[cfg(feature = "std")]
+use std::time::Duration;
+#[cfg(feature = "std")]
+use std::panic::Location;
+
+#[cfg(feature = "binary")]
+mod binary;
+#[cfg(feature = "std")]
+mod burst;
+#[cfg(feature = "std")]
+mod caller;
+#[cfg(feature = "std")]
+mod capture;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+mod console;
+#[cfg(feature = "std")]
+mod correlation;
+#[cfg(feature = "no_std")]
+mod core_fmt;
+#[cfg(feature = "metrics")]
+mod counters;
+#[cfg(feature = "std")]
+mod event;
+#[cfg(feature = "std")]
+mod format;
+#[cfg(feature = "gelf")]
+mod gelf;
+#[cfg(feature = "std")]
+mod hexdump;
+#[cfg(feature = "std")]
+mod line;
+#[cfg(feature = "std")]
+mod logger;
+#[cfg(feature = "std")]
+mod named;
+#[cfg(feature = "std")]
+mod network;
+#[cfg(feature = "std")]
+mod parse;
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
+mod redact;
+#[cfg(feature = "std")]
+mod resolved;
+#[cfg(feature = "sentry")]
+mod reporting;
+#[cfg(feature = "std")]
+mod sample;
+#[cfg(feature = "std")]
+mod sink;
+#[cfg(feature = "std")]
+mod span;
+#[cfg(feature = "std")]
+mod static_config;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+mod theme;
+#[cfg(feature = "tracing-mirror")]
+mod tracing_mirror;
+#[cfg(feature = "std")]
+mod validate;
+
+#[cfg(feature = "std")]
+use format::RecordFormatter;
+#[cfg(feature = "std")]
+use logger::{Backend, RimpLogger};
+
+#[cfg(feature = "binary")]
+pub use binary::BinaryRecord;
+#[cfg(feature = "std")]
+pub use burst::BurstPolicy;
+#[cfg(feature = "std")]
+pub use caller::__log_caller;
+#[cfg(feature = "std")]
+pub use clock::{Clock, SystemClock, __log_at_time};
+#[cfg(feature = "std")]
+pub use console::{ansi_supported, ColorMode, RedrawCallback, StdoutBuffering, SuspendCallback};
+#[cfg(feature = "indicatif")]
+pub use console::indicatif_suspend;
+#[cfg(feature = "std")]
+pub use correlation::{new_correlation_id, CorrelationGuard};
+#[cfg(feature = "no_std")]
+pub use core_fmt::CoreFormatter;
+#[cfg(feature = "metrics")]
+pub use counters::MetricsConfig;
+#[cfg(feature = "std")]
+pub use event::LogEvent;
+#[cfg(feature = "std")]
+pub use hexdump::{HEXDUMP_MAX_BYTES, __hexdump};
+#[cfg(feature = "std")]
+pub use line::LogLine;
+#[cfg(feature = "std")]
+pub use named::NamedLogger;
+#[cfg(feature = "std")]
+pub use network::NetworkSink;
+#[cfg(feature = "std")]
+pub use redact::Redactor;
+#[cfg(feature = "std")]
+pub use resolved::{effective_config, ResolvedConfig};
+#[cfg(feature = "std")]
+pub use sample::__sample;
+#[cfg(feature = "std")]
+pub use sink::{MirrorCallback, SinkConfig, SinkTarget};
+#[cfg(feature = "redact")]
+pub use redact::{card_number_redactor, regex_redactor};
+#[cfg(feature = "sentry")]
+pub use reporting::SentryConfig;
+#[cfg(feature = "std")]
+pub use span::SpanGuard;
+#[cfg(feature = "std")]
+pub use static_config::StaticConfig;
+#[cfg(feature = "std")]
+pub use theme::ColorTheme;
+#[cfg(feature = "std")]
+pub use validate::ConfigError;
+#[cfg(feature = "std")]
+pub use colored::Color;
 
 /// Logger builder
 /// 
 /// # Parameters
 /// - `level`: Log level, such as info, error, warn, debug, trace (default is `info`)
 /// - `only_project_logs`: Whether to output only project logs, not external module logs (default is `false`)
-/// - `path_depth`: Path display depth (default is `1`)
+/// - `path_depth`: How many components of the file path to display, a plain number converts with `.into()` (default is `PathDepth::Full`)
 /// - `time_format`: Time format such as `%Y-%m-%d %H:%M:%S` (default is `%Y-%m-%d %H:%M:%S`)
-/// - `preset`: Logger preset such as `FULL`, `THREAD`, `SIMPLE` (default is `FULL`)
+/// - `preset`: Logger preset such as `FULL`, `THREAD`, `SIMPLE`, `BINARY`, `JSON` (default is `FULL`)
+/// - `path_fallback`: How to display file paths without a `src` directory (default is `FileNameOnly`)
+/// - `file`: File to append the logs to instead of the console (default is `None`)
+/// - `highlight_rules`: Substrings of the message to color, such as `("FAILED", Color::Red)` (default is empty)
+/// - `highlight_case_insensitive`: Whether `highlight_rules` ignore ASCII case (default is `false`)
+/// - `collapse_consecutive`: Whether to suppress a line identical to the previous one (default is `false`)
+/// - `align_message`: Whether to start all messages at the same column (default is `false`)
+/// - `message_column`: Column to align messages at, `0` uses the widest prefix seen so far (default is `0`)
+/// - `watch_level_file`: File containing a level name, polled to change the level at runtime (default is `None`)
+/// - `redactor`: Function rewriting every message before it is written, for all presets and sinks (default is `None`)
+/// - `log_config_on_init`: Whether to log the effective configuration at info level after init (default is `false`)
+/// - `sanitize_messages`: Whether to escape newlines, carriage returns and ANSI escapes inside messages (default is `false`)
+/// - `external_detail`: What to show of records from external crates (default is `TargetAndPath`)
+/// - `color_theme`: Colors of the level labels (default is `ColorTheme::Default`)
+/// - `multiline`: Whether to indent the continuation lines of multiline messages under the message (default is `false`)
+/// - `session_separator`: Whether to write a banner with the time and PID at init when `file` already has content (default is `false`)
+/// - `file_header`: Line written to `file` when it is opened, supports `{time}`, `{pid}`, `{name}` and `{version}` placeholders (default is `None`)
+/// - `file_footer`: Line written to `file` when the [`FlushGuard`] is dropped, supports the same placeholders (default is `None`)
+/// - `numeric_level`: Whether to show levels as syslog severities, error is `3`, warn `4`, info `6`, debug and trace `7` (default is `false`)
+/// - `clock`: Source of the timestamps, also settable with [`LoggerBuilder::clock`] (default is [`SystemClock`])
+/// - `target_files`: Files to append the records whose target starts with a prefix to, such as `("my_app::db", "db.log")` (default is empty)
+/// - `target_files_only`: Whether records routed by `target_files` skip the main sink (default is `false`)
+/// - `short_level`: Whether to show levels as single letters `E`, `W`, `I`, `D` and `T` (default is `false`)
+/// - `color`: When to write colors, `None` uses the `RIMPLOG_COLOR` environment variable (default is `None`)
+/// - `trim_trailing`: Whether to remove whitespace at the end of lines, such as the space after an empty message, except with `RAW` (default is `true`)
+/// - `show_delta`: Whether to show the milliseconds since the previous record after the timestamp, such as `+123ms` (default is `false`)
+/// - `show_module_path`: Whether to show the module path of records as a segment before the message (default is `false`)
+/// - `progress_aware`: Whether to clear the current terminal line before each record and call `progress_redraw` after it (default is `false`)
+/// - `progress_redraw`: Function drawing the progress bar again after a record when `progress_aware` is set (default is `None`)
+/// - `progress_suspend`: Function hiding the progress bars while it runs the console and sink writes it is given, such as the one of `indicatif_suspend` (default is `None`)
+/// - `split_streams`: Whether to write records less severe than `stderr_level` to stdout instead of stderr (default is `false`)
+/// - `stderr_level`: Least severe level written to stderr when `split_streams` is set (default is `Warn`)
+/// - `stdout_buffering`: How records written to stdout by `split_streams`, `SinkTarget::Stdout` sinks and the fallback of `file` are buffered (default is `None`, line-buffered on a terminal and block-buffered otherwise for `split_streams`, and line-buffered for the others)
+/// - `line_prefix`: Text put at the start of every line, such as a shard id (default is `None`)
+/// - `line_suffix`: Text put at the end of every line, before the newline (default is `None`)
+/// - `level_source`: How `RUST_LOG` combines with `level` (default is `EnvThenBuilder`)
+/// - `warn_env_override`: Whether to warn at init when `RUST_LOG` overrides `level` (default is `false`)
+/// - `network_sink`: Network destination also receiving every line, UDP, TCP or a Unix domain socket (default is `None`)
+/// - `batch`: Send `network_sink` lines in batches of up to this many lines or this much time (default is `None`)
+/// - `level_bg_colors`: Background colors of the level labels by level, such as `Color::White` for errors (default is `None`)
+/// - `debug_file`: File receiving every record up to its own level, regardless of `level` (default is `None`)
+/// - `capture_stderr`: Whether to log the lines written directly to stderr as warnings, Unix only (default is `false`)
+/// - `span_timing_summary`: Whether to log the number of runs, total and average duration of every span name when the [`FlushGuard`] is dropped (default is `false`)
+/// - `timestamp_color`: Color of the timestamps, `None` leaves them uncolored (default is `Cyan`)
+/// - `path_color`: Color of the file path of the location, `None` leaves it uncolored (default is `Yellow`)
+/// - `line_color`: Color of the line number of the location, `None` leaves it uncolored (default is `Yellow`)
+/// - `thread_name_map`: Whether to show thread names as small stable indexes such as `#3`, printing which name an index stands for once (default is `false`)
+/// - `burst_policy`: Keep the first records of every target, then only sample them (default is `None`)
+/// - `target_width`: Width the target of external records is padded or truncated to (default is `None`)
+/// - `sinks`: Additional sinks with a preset and a level of their own, such as a `SIMPLE` mirror on stdout (default is empty)
+/// - `ansi_reset`: Whether to end every line with an ANSI reset when writing colors, so escapes left open by messages don't bleed (default is `true`)
+/// - `show_cpu_time`: Whether to show the CPU time used by the process so far after the timestamp, such as `cpu=1.250s`, Unix only (default is `false`)
+/// - `build_id`: Build identifier shown as a segment before the message, such as a git commit (default is `None`)
+/// - `pad_level`: Whether to pad level names to five columns, after the brackets around them so the messages stay aligned, the one column labels of `short_level` and `numeric_level` need no padding (default is `true`)
+/// - `hyperlink_paths`: Whether to make the `file:line` of `FULL` a clickable OSC 8 hyperlink in terminals supporting them (default is `false`)
+/// - `hyperlink_url`: URL opened by the links of `hyperlink_paths`, with `{path}` and `{line}` replaced (default is `file://{path}`)
+/// - `max_args_len`: Number of characters the rendered message is cut to once redacted and sanitized, closing the brackets left open (default is `None`)
+/// - `level_remap`: Levels to change for the records whose target starts with a prefix, such as `("noisy_crate", Level::Warn, Level::Debug)` (default is empty)
+/// - `exit_summary`: Line logged when the [`FlushGuard`] is dropped, supports `{errors}`, `{warnings}`, `{infos}`, `{debugs}`, `{traces}` and `{duration}` placeholders, such as `summary: errors={errors} warnings={warnings} duration={duration}` (default is `None`)
+/// - `max_allowed_level`: Most verbose level ever written, capping `level`, `RUST_LOG`, the sinks and runtime level changes (default is `None`)
+/// - `record_mirror`: Function also called with every written record, such as to emit it as a `tracing` event (default is `None`)
+/// - `channel_sink`: Channel also receiving every written record as a [`LogEvent`] instead of text (default is `None`)
+/// - `condense_timestamp`: Whether to replace the timestamp with spaces while the second doesn't change, keeping the columns (default is `false`)
+/// - `timezone`: IANA time zone name such as `America/New_York` used for timestamps instead of local time, requires the `tz` feature (default is `None`)
+/// - `metrics`: Counters incremented for every record, requires the `metrics` feature (default is `log_records_total` labelled by level)
+/// - `show_task_id`: Whether to append the id of the current Tokio task to the thread name, requires the `tokio` feature (default is `false`)
+/// - `sort_kv`: Whether to sort the key-value pairs of records by key, requires the `kv` feature (default is `false`)
+/// - `target_regex`: Regex a record's target must match to be logged to any output, requires the `regex` feature (default is `None`)
+/// - `sentry`: Records forwarded to Sentry, requires the `sentry` feature (default is errors as events and warnings as breadcrumbs)
+/// - `prettify_json_messages`: Whether to indent messages that are JSON documents on the console, requires the `json` feature (default is `false`)
+/// - `gelf`: Graylog input receiving every record as a GELF message, requires the `gelf` feature (default is `None`)
+/// - `tracing_mirror`: Whether to also emit every written record as a `tracing` event, requires the `tracing-mirror` feature (default is `true`)
+#[cfg(feature = "std")]
+#[derive(Clone)]
 pub struct LoggerBuilder {
     pub level: String,
     pub only_project_logs: bool,
-    pub path_depth: usize,
+    pub path_depth: PathDepth,
     pub time_format: String,
     pub preset: LoggerPreset,
+    pub path_fallback: PathFallback,
+    pub file: Option<PathBuf>,
+    pub highlight_rules: Vec<(String, Color)>,
+    pub highlight_case_insensitive: bool,
+    pub collapse_consecutive: bool,
+    pub align_message: bool,
+    pub message_column: usize,
+    pub watch_level_file: Option<PathBuf>,
+    pub redactor: Option<Redactor>,
+    pub log_config_on_init: bool,
+    pub sanitize_messages: bool,
+    pub external_detail: ExternalDetail,
+    pub color_theme: ColorTheme,
+    pub multiline: bool,
+    pub session_separator: bool,
+    pub file_header: Option<String>,
+    pub file_footer: Option<String>,
+    pub numeric_level: bool,
+    pub clock: Arc<dyn Clock>,
+    pub target_files: Vec<(String, PathBuf)>,
+    pub target_files_only: bool,
+    pub short_level: bool,
+    pub color: Option<ColorMode>,
+    pub trim_trailing: bool,
+    pub show_delta: bool,
+    pub show_module_path: bool,
+    pub progress_aware: bool,
+    pub progress_redraw: Option<RedrawCallback>,
+    pub progress_suspend: Option<SuspendCallback>,
+    pub split_streams: bool,
+    pub stderr_level: log::Level,
+    pub stdout_buffering: Option<StdoutBuffering>,
+    pub line_prefix: Option<String>,
+    pub line_suffix: Option<String>,
+    pub level_source: LevelSource,
+    pub warn_env_override: bool,
+    pub network_sink: Option<NetworkSink>,
+    pub batch: Option<(usize, Duration)>,
+    pub level_bg_colors: Option<HashMap<log::Level, Color>>,
+    pub debug_file: Option<(PathBuf, log::LevelFilter)>,
+    pub capture_stderr: bool,
+    pub span_timing_summary: bool,
+    pub timestamp_color: Option<Color>,
+    pub path_color: Option<Color>,
+    pub line_color: Option<Color>,
+    pub thread_name_map: bool,
+    pub burst_policy: Option<BurstPolicy>,
+    pub target_width: Option<usize>,
+    pub sinks: Vec<SinkConfig>,
+    pub ansi_reset: bool,
+    pub show_cpu_time: bool,
+    pub build_id: Option<String>,
+    pub pad_level: bool,
+    pub hyperlink_paths: bool,
+    pub hyperlink_url: String,
+    pub max_args_len: Option<usize>,
+    pub level_remap: Vec<(String, log::Level, log::Level)>,
+    pub exit_summary: Option<String>,
+    pub max_allowed_level: Option<log::LevelFilter>,
+    pub record_mirror: Option<MirrorCallback>,
+    pub channel_sink: Option<Sender<LogEvent>>,
+    pub condense_timestamp: bool,
+    #[cfg(feature = "tz")]
+    pub timezone: Option<String>,
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<MetricsConfig>,
+    #[cfg(feature = "tokio")]
+    pub show_task_id: bool,
+    #[cfg(feature = "kv")]
+    pub sort_kv: bool,
+    #[cfg(feature = "regex")]
+    pub target_regex: Option<String>,
+    #[cfg(feature = "sentry")]
+    pub sentry: Option<SentryConfig>,
+    #[cfg(feature = "json")]
+    pub prettify_json_messages: bool,
+    #[cfg(feature = "gelf")]
+    pub gelf: Option<NetworkSink>,
+    #[cfg(feature = "tracing-mirror")]
+    pub tracing_mirror: bool,
 }
 
+#[cfg(feature = "std")]
 impl Default for LoggerBuilder {
     fn default() -> Self {
         LoggerBuilder {
             level: "info".to_string(),
             only_project_logs: false,
-            path_depth: 0,
+            path_depth: PathDepth::Full,
             time_format: "%Y-%m-%d %H:%M:%S".to_string(),
             preset: LoggerPreset::FULL,
+            path_fallback: PathFallback::FileNameOnly,
+            file: None,
+            highlight_rules: Vec::new(),
+            highlight_case_insensitive: false,
+            collapse_consecutive: false,
+            align_message: false,
+            message_column: 0,
+            watch_level_file: None,
+            redactor: None,
+            log_config_on_init: false,
+            sanitize_messages: false,
+            external_detail: ExternalDetail::TargetAndPath,
+            color_theme: ColorTheme::Default,
+            multiline: false,
+            session_separator: false,
+            file_header: None,
+            file_footer: None,
+            numeric_level: false,
+            clock: Arc::new(SystemClock),
+            target_files: Vec::new(),
+            target_files_only: false,
+            short_level: false,
+            color: None,
+            trim_trailing: true,
+            show_delta: false,
+            show_module_path: false,
+            progress_aware: false,
+            progress_redraw: None,
+            progress_suspend: None,
+            split_streams: false,
+            stderr_level: log::Level::Warn,
+            stdout_buffering: None,
+            line_prefix: None,
+            line_suffix: None,
+            level_source: LevelSource::EnvThenBuilder,
+            warn_env_override: false,
+            network_sink: None,
+            batch: None,
+            level_bg_colors: None,
+            debug_file: None,
+            capture_stderr: false,
+            span_timing_summary: false,
+            timestamp_color: Some(Color::Cyan),
+            path_color: Some(Color::Yellow),
+            line_color: Some(Color::Yellow),
+            thread_name_map: false,
+            burst_policy: None,
+            target_width: None,
+            sinks: Vec::new(),
+            ansi_reset: true,
+            show_cpu_time: false,
+            build_id: None,
+            pad_level: true,
+            hyperlink_paths: false,
+            hyperlink_url: "file://{path}".to_string(),
+            max_args_len: None,
+            level_remap: Vec::new(),
+            exit_summary: None,
+            max_allowed_level: None,
+            record_mirror: None,
+            channel_sink: None,
+            condense_timestamp: false,
+            #[cfg(feature = "tz")]
+            timezone: None,
+            #[cfg(feature = "metrics")]
+            metrics: Some(MetricsConfig::default()),
+            #[cfg(feature = "tokio")]
+            show_task_id: false,
+            #[cfg(feature = "kv")]
+            sort_kv: false,
+            #[cfg(feature = "regex")]
+            target_regex: None,
+            #[cfg(feature = "sentry")]
+            sentry: Some(SentryConfig::default()),
+            #[cfg(feature = "json")]
+            prettify_json_messages: false,
+            #[cfg(feature = "gelf")]
+            gelf: None,
+            #[cfg(feature = "tracing-mirror")]
+            tracing_mirror: true,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl LoggerBuilder {
+    /// Create a builder preconfigured for an [`Environment`], to customize further
+    ///
+    /// - `Development`: debug level, `FULL` preset and colors on terminals
+    /// - `Production`: info level, `JSON` preset, RFC 3339 timestamps and no colors
+    /// - `Test`: debug level, `SIMPLE` preset, no colors and `RUST_LOG` ignored, for stable output
+    ///
+    /// Without the `json` feature, `Production` uses the `THREAD` preset with sanitized messages
+    /// instead, one plain text line per record. Set `file` to write them somewhere else than stderr.
+    pub fn preset_for(environment: Environment) -> Self {
+        match environment {
+            Environment::Development => LoggerBuilder {
+                level: "debug".to_string(),
+                preset: LoggerPreset::FULL,
+                ..LoggerBuilder::default()
+            },
+            #[cfg(feature = "json")]
+            Environment::Production => LoggerBuilder {
+                level: "info".to_string(),
+                preset: LoggerPreset::JSON,
+                color: Some(ColorMode::Never),
+                ..LoggerBuilder::default()
+            }
+            .time_preset(TimePreset::Rfc3339),
+            #[cfg(not(feature = "json"))]
+            Environment::Production => LoggerBuilder {
+                level: "info".to_string(),
+                preset: LoggerPreset::THREAD,
+                color: Some(ColorMode::Never),
+                sanitize_messages: true,
+                ..LoggerBuilder::default()
+            }
+            .time_preset(TimePreset::Rfc3339),
+            Environment::Test => LoggerBuilder {
+                level: "debug".to_string(),
+                preset: LoggerPreset::SIMPLE,
+                color: Some(ColorMode::Never),
+                level_source: LevelSource::BuilderOnly,
+                ..LoggerBuilder::default()
+            },
+        }
+    }
+
+    /// Set the preset, such as `LoggerBuilder::default().preset(LoggerPreset::SIMPLE)`
+    pub fn preset(mut self, preset: LoggerPreset) -> Self {
+        self.preset = preset;
+        self
+    }
+
+    /// Set `time_format` from a known-good [`TimePreset`]
+    pub fn time_preset(mut self, preset: TimePreset) -> Self {
+        self.time_format = preset.time_format().to_string();
+        self
+    }
+
+    /// Set the colors of the level labels from a built-in [`ColorTheme`]
+    pub fn color_theme(mut self, theme: ColorTheme) -> Self {
+        self.color_theme = theme;
+        self
+    }
+
+    /// Set the [`Clock`] timestamps are taken from, such as a [`FixedClock`](testing::FixedClock) in tests
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+/// Common time formats for `time_format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimePreset {
+    /// `2024-10-18T09:30:00+08:00`
+    Rfc3339,
+    /// `9:30 AM`
+    Kitchen,
+    /// `2024-10-18`
+    DateOnly,
+    /// `09:30:00`
+    TimeOnly,
+    /// ISO week date such as `2024-W42-5`
+    IsoWeek,
+    /// Year and day of the year such as `2024-292`
+    DayOfYear,
+}
+
+impl TimePreset {
+    /// Get the chrono format string of the preset
+    pub fn time_format(self) -> &'static str {
+        match self {
+            TimePreset::Rfc3339 => "%Y-%m-%dT%H:%M:%S%:z",
+            TimePreset::Kitchen => "%-I:%M %p",
+            TimePreset::DateOnly => "%Y-%m-%d",
+            TimePreset::TimeOnly => "%H:%M:%S",
+            TimePreset::IsoWeek => "%G-W%V-%u",
+            TimePreset::DayOfYear => "%Y-%j",
         }
     }
 }
 
+/// Where the program runs, for [`LoggerBuilder::preset_for`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Production,
+    Test,
+}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LoggerPreset {
     FULL,
     THREAD,
     SIMPLE,
+    /// Only the message, without a timestamp, level or location, for scripts that only need level filtering
+    RAW,
+    /// Length-prefixed [`BinaryRecord`]s, meant for `file`, decode them with the `rimplog-cat` example
+    #[cfg(feature = "binary")]
+    BINARY,
+    /// One JSON object per line with the timestamp, level, target, thread, location and message
+    #[cfg(feature = "json")]
+    JSON,
+}
+
+impl LoggerPreset {
+    #[cfg(feature = "std")]
+    /// Whether the preset writes binary records instead of text lines
+    pub(crate) fn is_binary(self) -> bool {
+        #[cfg(feature = "binary")]
+        if self == LoggerPreset::BINARY {
+            return true;
+        }
+        false
+    }
+
+    #[cfg(feature = "std")]
+    /// Whether the preset writes JSON objects instead of text lines
+    pub(crate) fn is_json(self) -> bool {
+        #[cfg(feature = "json")]
+        if self == LoggerPreset::JSON {
+            return true;
+        }
+        false
+    }
+}
+
+/// How to display a file path that has no `src` directory
+///
+/// Such paths usually come from dependencies in `~/.cargo/registry` and are
+/// absolute, so printing them in full leaks the build machine's directory
+/// layout (user name, home directory) into the logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathFallback {
+    /// Keep all components of the path, still applying `path_depth`
+    FullPath,
+    /// Keep only the file name
+    FileNameOnly,
+    /// Print the path exactly as reported by the record
+    AsIs,
+}
+
+/// How many components of a file path to display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathDepth {
+    /// The whole path from the `src` directory
+    #[default]
+    Full,
+    /// The last components of the path, such as `db/pool.rs` for `2`, `0` hides the path
+    ///
+    /// Any depth of at least the number of components, up to `usize::MAX`, shows the whole path.
+    Components(usize),
+    /// Only the file name
+    FileOnly,
+}
+
+impl From<usize> for PathDepth {
+    /// Convert a depth from before [`PathDepth`] existed, where `0` means the full path
+    fn from(depth: usize) -> Self {
+        match depth {
+            0 => PathDepth::Full,
+            depth => PathDepth::Components(depth),
+        }
+    }
+}
+
+/// How the `RUST_LOG` environment variable combines with `level`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LevelSource {
+    /// `RUST_LOG` directives for modules, such as `my_app::db=trace`, take precedence, `level`
+    /// applies to everything else
+    #[default]
+    EnvThenBuilder,
+    /// `RUST_LOG` is ignored
+    BuilderOnly,
+    /// `RUST_LOG` decides alone, `level` only applies when it is unset
+    EnvOnly,
+}
+
+/// What to show of records from external crates, whose target is not the project
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExternalDetail {
+    /// Show the target and the file path, such as `[hyper] src/proto.rs:12`
+    TargetAndPath,
+    /// Show only the target, such as `hyper`
+    TargetOnly,
+    /// Drop the records entirely, regardless of their level, before any sink, mirror or counter sees them
+    Hidden,
 }
 
 /// Initialize the logger
 /// 
 /// # Parameters
 /// - `logger_builder`: Logger builder
+///
+/// # Panics
+/// If a logger is already installed, use [`try_init_logger`] when that can happen.
+#[cfg(feature = "std")]
 pub fn init_logger(logger_builder: LoggerBuilder) {
-    let level = logger_builder.level.to_lowercase();
-    let only_project_logs = logger_builder.only_project_logs;
-    let path_depth = logger_builder.path_depth;
-    let time_format = logger_builder.time_format;
-    let preset = logger_builder.preset;
-
-    let project_name = Arc::new(env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "unknown".to_string()));
-
-    let env = Env::default().filter_or("RUST_LOG", level.clone());
-    let mut builder = Builder::from_env(env);
-
-    let project_name_clone = Arc::clone(&project_name);
-    builder.format(move |buf, record| {
-        let file_path = record.file().unwrap_or("unknown");
-        let project_relative_path = get_project_relative_path(file_path, path_depth);
-        let line = record.line().unwrap_or(0);
-
-        let level = match record.level() {
-            log::Level::Error => "ERROR".red().bold(),
-            log::Level::Warn => "WARN ".yellow().bold(),
-            log::Level::Info => "INFO ".green().bold(),
-            log::Level::Debug => "DEBUG".blue().bold(),
-            log::Level::Trace => "TRACE".magenta().bold(),
-        };
+    install_logger(logger_builder).expect("init_logger should not be called after logger initialized");
+}
 
-        let thread_name = thread::current().name().unwrap_or("unknown").to_string();
-        let thread_colored = if thread_name == "main" {
-            thread_name.bright_green()
-        } else {
-            thread_name.bright_blue()
-        };
+/// Initialize the logger unless a logger is already installed
+///
+/// Safe to call from several threads at once, such as from lazy initializers in tests.
+/// Only the first call builds and installs the logger, the others return an error without
+/// touching `file` or any other sink.
+///
+/// # Parameters
+/// - `logger_builder`: Logger builder
+#[cfg(feature = "std")]
+pub fn try_init_logger(logger_builder: LoggerBuilder) -> Result<(), AlreadyInitialized> {
+    install_logger(logger_builder).map(|_| ())
+}
 
-        let project = if record.target().starts_with(&*project_name_clone) {
-            format!("{}",
-                project_relative_path.yellow())
-        } else {
-            format!("[{}] {}",
-                record.target().yellow(), 
-                project_relative_path.yellow())
-        };
+/// Error returned by [`try_init_logger`] when a logger is already installed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlreadyInitialized;
 
-        let timestamp = Local::now().format(&time_format).to_string().cyan();
-
-        let log_message = match preset {
-            LoggerPreset::FULL => {
-                format!(
-                    "{} {} [{}] [{}:{}] {}",
-                    timestamp,
-                    level,
-                    thread_colored,
-                    project,
-                    line.to_string().yellow(),
-                    record.args()
-                )
-            }
-            LoggerPreset::THREAD => {
-                format!(
-                    "{} {} [{}] {}",
-                    timestamp,
-                    level,
-                    thread_colored,
-                    record.args()
-                )
-            }
-            LoggerPreset::SIMPLE => {
-                format!(
-                    "[ {} {}] {}",
-                    timestamp,
-                    level,
-                    record.args()
-                )
-            }
-        };
+impl fmt::Display for AlreadyInitialized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a logger is already installed")
+    }
+}
 
-        // Write the log message, but do not add a newline
-        write!(buf, "{}", log_message)
-    });
+#[cfg(feature = "std")]
+impl std::error::Error for AlreadyInitialized {}
+
+/// Initialize the logger from a compact configuration such as `level=debug,preset=thread,depth=2`
+///
+/// See [`LoggerBuilder::parse`] for the supported keys.
+#[cfg(feature = "std")]
+pub fn init_logger_from_str(config: &str) {
+    init_logger(LoggerBuilder::parse(config));
+}
+
+/// Initialize the logger and return a guard that finishes it when dropped
+///
+/// Dropping the guard flushes the logger and writes `file_footer`, so keep it alive
+/// until the end of `main`.
+///
+/// # Parameters
+/// - `logger_builder`: Logger builder
+#[cfg(feature = "std")]
+pub fn init_logger_with_guard(logger_builder: LoggerBuilder) -> FlushGuard {
+    FlushGuard {
+        logger: install_logger(logger_builder)
+            .expect("init_logger_with_guard should not be called after logger initialized"),
+    }
+}
+
+/// Guard returned by [`init_logger_with_guard`], flushes the logger and writes `file_footer` when dropped
+///
+/// With `span_timing_summary` and `exit_summary`, the span timings and then the summary line are logged first.
+#[cfg(feature = "std")]
+#[must_use = "the logger is finished as soon as the guard is dropped"]
+pub struct FlushGuard {
+    logger: &'static RimpLogger,
+}
+
+#[cfg(feature = "std")]
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        span::log_timing_summary();
+        self.logger.log_exit_summary();
+        self.logger.finish();
+    }
+}
+
+/// Build the logger without installing it, for frameworks that install loggers themselves
+///
+/// Install it with `log::set_boxed_logger`, and set the matching maximum level from
+/// [`max_level`] with `log::set_max_level`. As rimplog doesn't own the logger,
+/// [`set_max_level`], [`effective_config`] and `watch_level_file` have no effect on it, and
+/// `log_config_on_init` is ignored.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::LoggerBuilder;
+///
+/// let config = LoggerBuilder::default();
+/// log::set_max_level(rimplog::max_level(&config));
+/// log::set_boxed_logger(rimplog::build_logger(config)).unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub fn build_logger(logger_builder: LoggerBuilder) -> Box<dyn log::Log> {
+    console::init_ansi_support();
+    let logger = build(logger_builder, None).logger;
+    logger.start();
+    Box::new(logger)
+}
+
+/// Get the most verbose level the logger built from `logger_builder` writes, for `log::set_max_level`
+///
+/// This accounts for `RUST_LOG` module directives, `debug_file`, `sinks`, `max_allowed_level` and
+/// `level_remap`. An invalid `level` counts as info.
+#[cfg(feature = "std")]
+pub fn max_level(logger_builder: &LoggerBuilder) -> log::LevelFilter {
+    let level = logger_builder.level.to_lowercase().parse().unwrap_or(log::LevelFilter::Info);
+    let filter = logger::filtered_builder(
+        logger_builder.level_source,
+        level,
+        logger_builder.only_project_logs,
+        project_name(),
+    )
+    .build()
+    .filter();
+    let debug_level = logger_builder.debug_file.as_ref().map(|(_, level)| *level);
+    let filter = logger_builder
+        .sinks
+        .iter()
+        .map(|sink| sink.level)
+        .chain(debug_level)
+        .fold(filter, Ord::max);
+    let filter = match logger_builder.max_allowed_level {
+        Some(max_allowed_level) => filter.min(max_allowed_level),
+        None => filter,
+    };
+    logger::remapped_filter(filter, &logger_builder.level_remap)
+}
+
+/// Build the logger and install it as the global logger
+#[cfg(feature = "std")]
+fn install_logger(logger_builder: LoggerBuilder) -> Result<&'static RimpLogger, AlreadyInitialized> {
+    // Claim init before building, so racing calls don't open the sinks twice
+    static INIT_CLAIMED: AtomicBool = AtomicBool::new(false);
+    if INIT_CLAIMED.swap(true, Ordering::AcqRel) {
+        return Err(AlreadyInitialized);
+    }
+
+    console::init_ansi_support();
+    if logger_builder.span_timing_summary {
+        span::enable_timings();
+    }
+    let built = build(logger_builder, None);
+    built.logger.start();
+
+    let logger = logger::install(built.logger).map_err(|_| AlreadyInitialized)?;
+
+    resolved::store(built.resolved);
+
+    if let Some(path) = built.watch_level_file {
+        logger::watch_level_file(logger, path);
+    }
+
+    if let Some(config_summary) = built.config_summary {
+        log::info!(target: project_name(), "{}\n", config_summary);
+    }
+
+    Ok(logger)
+}
+
+/// Replace the `{time}`, `{pid}`, `{name}` and `{version}` placeholders of a header or footer template
+#[cfg(feature = "std")]
+pub(crate) fn render_file_template(template: &str, time_format: &str) -> String {
+    template
+        .replace("{time}", &chrono::Local::now().format(time_format).to_string())
+        .replace("{pid}", &process::id().to_string())
+        .replace("{name}", project_name())
+        .replace("{version}", &env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "unknown".to_string()))
+}
+
+/// A logger built from a [`LoggerBuilder`] that is not installed yet
+#[cfg(feature = "std")]
+struct BuiltLogger {
+    logger: RimpLogger,
+    watch_level_file: Option<PathBuf>,
+    config_summary: Option<String>,
+    resolved: ResolvedConfig,
+}
+
+/// Build a logger from a [`LoggerBuilder`]
+///
+/// `original_stderr` is the stderr captured by the configuration being replaced, it's written
+/// to instead of capturing stderr again. What only happens once per logger, such as writing
+/// `file_header` and setting the colors, is left to [`RimpLogger::start`].
+#[cfg(feature = "std")]
+fn build(mut logger_builder: LoggerBuilder, original_stderr: Option<File>) -> BuiltLogger {
+    let level = logger_builder.level.to_lowercase();
+    let only_project_logs = logger_builder.only_project_logs;
+    let file = logger_builder.file.take();
+    let watch_level_file = logger_builder.watch_level_file.take();
+    let debug_file = logger_builder.debug_file.take();
+    let burst_policy = logger_builder.burst_policy;
+    let target_files = std::mem::take(&mut logger_builder.target_files);
+    #[cfg(feature = "metrics")]
+    let metrics = logger_builder.metrics.take();
+    #[cfg(feature = "gelf")]
+    let gelf = logger_builder.gelf.take().map(gelf::GelfWriter::new);
 
     // Parse the log level and handle any errors
     let parsed_level = level.parse::<log::LevelFilter>().unwrap_or_else(|_| {
@@ -135,49 +819,329 @@ pub fn init_logger(logger_builder: LoggerBuilder) {
         log::LevelFilter::Info
     });
 
-    if only_project_logs {
-        builder
-            .filter(None, log::LevelFilter::Off)
-            .filter(Some(&*project_name), parsed_level);
-    } else {
-        builder.filter(None, parsed_level);
+    // Fall back to stdout if the log file can't be opened, so logs are not lost
+    let mut sink = "console".to_string();
+    let mut file_fallback = false;
+    let file = file.and_then(|path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| {
+                eprintln!(
+                    "Failed to open log file '{}': {}, logging to stdout instead",
+                    path.display(),
+                    err
+                );
+                file_fallback = true;
+                sink = "stdout".to_string();
+            })
+            .inspect(|_| {
+                sink = format!("file '{}'", path.display());
+            })
+            .ok()
+    });
+
+    // JSON is only indented on the console, files keep one record per line
+    #[cfg(feature = "json")]
+    if file.is_some() {
+        logger_builder.prettify_json_messages = false;
     }
 
-    builder.init();
-}
+    let target_files: Vec<_> = target_files
+        .into_iter()
+        .filter_map(|(prefix, path)| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|err| {
+                    eprintln!(
+                        "Failed to open log file '{}' for target '{}': {}, skipping it",
+                        path.display(),
+                        prefix,
+                        err
+                    );
+                })
+                .map(|file| (prefix, Mutex::new(file)))
+                .ok()
+        })
+        .collect();
 
-/// Get the project relative path
-fn get_project_relative_path(file_path: &str, depth: usize) -> String {
-    let path = Path::new(file_path);
-    let components: Vec<&str> = path
-        .components()
-        .filter_map(|c| c.as_os_str().to_str())
+    let network = logger_builder
+        .network_sink
+        .clone()
+        .map(|sink| network::NetworkWriter::new(sink, logger_builder.batch));
+
+    let color_mode = console::color_mode(logger_builder.color);
+
+    if logger_builder.warn_env_override {
+        logger::warn_env_override(logger_builder.level_source, parsed_level);
+    }
+
+    let stdout = Arc::new(console::StdoutSink::new(logger_builder.stdout_buffering));
+    // Sinks and the `file` fallback only buffer when asked to, since nothing writes a block buffer
+    // out at exit without the guard or a flush
+    let sink_stdout = match logger_builder.stdout_buffering {
+        Some(_) => Arc::clone(&stdout),
+        None => Arc::new(console::StdoutSink::new(Some(StdoutBuffering::Line))),
+    };
+    // `debug_file` is a sink in the main preset
+    let debug_sink = debug_file.map(|(path, level)| SinkConfig {
+        preset: logger_builder.preset,
+        level,
+        target: SinkTarget::File(path),
+    });
+    let mut sinks: Vec<sink::Sink> = debug_sink
+        .into_iter()
+        .chain(std::mem::take(&mut logger_builder.sinks))
+        .filter_map(|config| {
+            let target = format!("{:?}", config.target);
+            sink::Sink::open(config, &logger_builder, color_mode, project_name(), &sink_stdout)
+                .map_err(|err| eprintln!("Failed to open sink {}: {}, skipping it", target, err))
+                .ok()
+        })
         .collect();
 
-    // Find the index of the "src" directory
-    let src_index = components.iter().position(|&c| c == "src");
+    let log_config_on_init = logger_builder.log_config_on_init;
+    let mut resolved = ResolvedConfig {
+        level: parsed_level,
+        max_level: parsed_level,
+        rust_log: env::var("RUST_LOG").ok(),
+        level_source: logger_builder.level_source,
+        preset: logger_builder.preset,
+        only_project_logs,
+        sink,
+        color: color_mode,
+    };
 
-    let relevant_components = if let Some(index) = src_index {
-        components.iter()
-            .skip(index)
-            .cloned()
-            .collect::<Vec<&str>>()
+    // Last, so the warnings above are still printed to the original stderr
+    let stderr = if original_stderr.is_some() {
+        original_stderr
+    } else if logger_builder.capture_stderr {
+        capture::capture_stderr()
+            .map_err(|err| eprintln!("Failed to capture stderr: {}, leaving it alone", err))
+            .ok()
     } else {
-        components.clone()
+        None
+    };
+    if let Some(stderr) = &stderr {
+        sinks.iter_mut().for_each(|sink| sink.use_original_stderr(stderr));
+    }
+    // JSON is only indented on the console, target files and the network keep one record per line
+    #[cfg(feature = "json")]
+    let plain_formatter = (logger_builder.prettify_json_messages && (!target_files.is_empty() || network.is_some())).then(|| {
+        let config = LoggerBuilder {
+            prettify_json_messages: false,
+            ..logger_builder.clone()
+        };
+        Arc::new(RecordFormatter::new(config, project_name().to_string()))
+    });
+    #[cfg(not(feature = "json"))]
+    let plain_formatter = None;
+
+    let backend = Backend {
+        formatter: Arc::new(RecordFormatter::new(logger_builder, project_name().to_string())),
+        plain_formatter,
+        only_project_logs,
+        project_name: project_name().to_string(),
+        file,
+        file_fallback,
+        stderr,
+        target_files: Arc::new(target_files),
+        color_mode,
+        network,
+        stdout,
+        sink_stdout,
+        sinks,
+        burst: burst_policy.map(burst::BurstSampler::new),
+        #[cfg(feature = "metrics")]
+        metrics,
+        #[cfg(feature = "gelf")]
+        gelf,
     };
 
-    let total = relevant_components.len();
-    if depth == 0 || depth >= total {
-        // If the depth is 0 or greater than or equal to the total number of components, return the full path
-        relevant_components.join("/")
-    } else {
-        // Keep the last `depth` components
-        relevant_components[(total - depth)..].join("/")
+    let logger = RimpLogger::new(backend, parsed_level);
+    resolved.max_level = logger.filter();
+
+    let config_summary = log_config_on_init.then(|| {
+        format!(
+            "rimplog initialized: level={}, preset={:?}, only_project_logs={}, RUST_LOG={}, sink={}",
+            resolved.level,
+            resolved.preset,
+            resolved.only_project_logs,
+            resolved.rust_log.as_deref().unwrap_or("unset"),
+            resolved.sink
+        )
+    });
+
+    BuiltLogger {
+        logger,
+        watch_level_file,
+        config_summary,
+        resolved,
+    }
+}
+
+/// Change the default level of the global logger at runtime, `RUST_LOG` module directives are kept
+///
+/// This only rebuilds the filter when the logger was installed by rimplog, such as with
+/// [`init_logger`]. Otherwise only `log`'s global maximum level is changed.
+#[cfg(feature = "std")]
+pub fn set_max_level(level: log::LevelFilter) {
+    match logger::installed() {
+        Some(logger) => logger.set_level(level),
+        None => log::set_max_level(level),
     }
 }
 
+/// Replace the configuration of the global logger, such as when a service reloads its config
+///
+/// The new configuration takes effect at once for every thread: the level, sinks and format
+/// are swapped together, after the current ones are flushed. This only works when the logger
+/// was installed by rimplog, otherwise it returns `false` and `logger_builder` is dropped.
+///
+/// Once stderr is captured by `capture_stderr`, it stays captured. `watch_level_file`,
+/// `span_timing_summary` and [`effective_config`] keep the configuration given at init.
+/// The `file_footer` of the current configuration is written, but `session_separator` and
+/// `file_header` are only written at init. Colors follow the `color` of the new configuration.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::{LoggerBuilder, LoggerPreset};
+///
+/// rimplog::init_logger(LoggerBuilder::default());
+/// // On SIGHUP
+/// rimplog::reconfigure(LoggerBuilder {
+///     preset: LoggerPreset::SIMPLE,
+///     level: "debug".to_string(),
+///     ..LoggerBuilder::default()
+/// });
+/// ```
+#[cfg(feature = "std")]
+pub fn reconfigure(logger_builder: LoggerBuilder) -> bool {
+    let Some(logger) = logger::installed() else {
+        return false;
+    };
+    logger.replace(build(logger_builder, logger.original_stderr()).logger);
+    true
+}
+
+/// Flush the active log sinks
+///
+/// This writes out stdout when it is block-buffered by `stdout_buffering`, and sends the
+/// pending batch of `network_sink`. The other sinks are written through as records are logged.
+#[cfg(feature = "std")]
+pub fn flush() {
+    log::logger().flush();
+}
+
+/// Get the project name used to tell project logs from external ones
+#[cfg(feature = "std")]
+fn project_name() -> &'static str {
+    static PROJECT_NAME: OnceLock<String> = OnceLock::new();
+    PROJECT_NAME.get_or_init(|| env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "unknown".to_string()))
+}
+
+/// Log a record with an explicit location
+#[cfg(feature = "std")]
+pub(crate) fn log_record(level: log::Level, target: &str, file: &str, line: u32, args: fmt::Arguments) {
+    if level <= log::max_level() {
+        __log_to(log::logger(), level, target, file, line, args);
+    }
+}
+
+/// Log a record with an explicit location to `logger`, used by [`log_scoped!`]
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn __log_to(logger: &dyn log::Log, level: log::Level, target: &str, file: &str, line: u32, args: fmt::Arguments) {
+    let metadata = log::Metadata::builder().level(level).target(target).build();
+    if logger.enabled(&metadata) {
+        logger.log(
+            &log::Record::builder()
+                .metadata(metadata)
+                .args(args)
+                .file(Some(file))
+                .line(Some(line))
+                .build(),
+        );
+    }
+}
+
+/// Get the displayed form of a record's file path, exposed for the benchmarks
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn __project_relative_path(file_path: &str, depth: PathDepth, fallback: PathFallback) -> std::borrow::Cow<'_, str> {
+    format::get_project_relative_path(file_path, depth, fallback)
+}
+
+/// Log the error of a `Result` and pass the `Result` through unchanged
+///
+/// A method can't see the module it is called from, so [`log_err`](LogErrExt::log_err) logs under
+/// the crate name as target, while [`log_err!`] logs under the calling module. The target decides
+/// which filters, `level_remap` entries and `external_detail` apply, so pass `module_path!()` to
+/// [`log_err_to`](LogErrExt::log_err_to) to log exactly like the macro. Both take the file and
+/// line of the caller.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::LogErrExt;
+///
+/// let value = "42".parse::<u32>().log_err(log::Level::Warn);
+/// let value = "42".parse::<u32>().log_err_to(module_path!(), log::Level::Warn);
+/// ```
+#[cfg(feature = "std")]
+pub trait LogErrExt {
+    /// Log the error (if any) at `level` under the crate name and return `self` unchanged
+    fn log_err(self, level: log::Level) -> Self;
+
+    /// Log the error (if any) at `level` under `target` and return `self` unchanged
+    fn log_err_to(self, target: &str, level: log::Level) -> Self;
+}
+
+#[cfg(feature = "std")]
+impl<T, E: fmt::Display> LogErrExt for Result<T, E> {
+    #[track_caller]
+    fn log_err(self, level: log::Level) -> Self {
+        self.log_err_to(project_name(), level)
+    }
+
+    #[track_caller]
+    fn log_err_to(self, target: &str, level: log::Level) -> Self {
+        if let Err(ref err) = self {
+            let location = Location::caller();
+            log_record(level, target, location.file(), location.line(), format_args!("{}\n", err));
+        }
+        self
+    }
+}
+
+/// Log a message at info level, ending it with a newline
+///
+/// The `log_*`, `_log_*` and `clog_*` families exist for every level. They all require a message,
+/// so an accidental blank line is a compile error:
+///
+/// ```compile_fail
+/// rimplog::log_info!();
+/// ```
+///
+/// An empty message is still a line, with nothing after the level:
+///
+/// ```
+/// rimplog::log_info!("");
+/// ```
+///
+/// The format string is passed through to `format_args!` unchanged, so a wrong number of
+/// arguments is reported on the placeholders of the call site, not inside rimplog:
+///
+/// ```compile_fail
+/// rimplog::log_info!("{} of {}", 1);
+/// ```
 #[macro_export]
 macro_rules! log_info {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::info!(target: module_path!(), "{}\n", format_args!($($arg)*));
     })
@@ -185,6 +1149,9 @@ macro_rules! log_info {
 
 #[macro_export]
 macro_rules! log_error {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::error!(target: module_path!(), "{}\n", format_args!($($arg)*));
     })
@@ -192,6 +1159,9 @@ macro_rules! log_error {
 
 #[macro_export]
 macro_rules! log_warn {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::warn!(target: module_path!(), "{}\n", format_args!($($arg)*));
     })
@@ -199,6 +1169,9 @@ macro_rules! log_warn {
 
 #[macro_export]
 macro_rules! log_debug {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::debug!(target: module_path!(), "{}\n", format_args!($($arg)*));
     })
@@ -206,6 +1179,9 @@ macro_rules! log_debug {
 
 #[macro_export]
 macro_rules! log_trace {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::trace!(target: module_path!(), "{}\n", format_args!($($arg)*));
     })
@@ -214,6 +1190,9 @@ macro_rules! log_trace {
 
 #[macro_export]
 macro_rules! _log_info {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::info!(target: module_path!(), "{}", format_args!($($arg)*));
     })
@@ -221,6 +1200,9 @@ macro_rules! _log_info {
 
 #[macro_export]
 macro_rules! _log_error {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::error!(target: module_path!(), "{}", format_args!($($arg)*));
     })
@@ -228,6 +1210,9 @@ macro_rules! _log_error {
 
 #[macro_export]
 macro_rules! _log_warn {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::warn!(target: module_path!(), "{}", format_args!($($arg)*));
     })
@@ -235,6 +1220,9 @@ macro_rules! _log_warn {
 
 #[macro_export]
 macro_rules! _log_debug {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::debug!(target: module_path!(), "{}", format_args!($($arg)*));
     })
@@ -242,11 +1230,619 @@ macro_rules! _log_debug {
 
 #[macro_export]
 macro_rules! _log_trace {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
     ($($arg:tt)*) => ({
         $crate::log::trace!(target: module_path!(), "{}", format_args!($($arg)*));
     })
 }
 
+/// Start a line logged at `level` as one record once it's dropped, built from fragments
+///
+/// Unlike successive `_log_*` calls, records of other threads can't land in the middle of
+/// the line. Fragments are added with `push` or `write!`, and a newline is added at the end.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_line;
+/// use std::fmt::Write;
+///
+/// let mut line = log_line!(log::Level::Info, "checking {} files...", 3);
+/// for name in ["a.txt", "b.txt", "c.txt"] {
+///     write!(line, " {}", name).unwrap();
+/// }
+/// line.push(format_args!(" done"));
+/// line.finish();
+/// ```
+#[macro_export]
+macro_rules! log_line {
+    ($level:expr $(,)?) => {
+        $crate::LogLine::new($level, module_path!(), file!(), line!())
+    };
+    ($level:expr, $($arg:tt)+) => ({
+        let mut line = $crate::LogLine::new($level, module_path!(), file!(), line!());
+        line.push(format_args!($($arg)+));
+        line
+    });
+}
+
+/// Like `log_info!`, but uses the crate name as the target instead of the full module path
+///
+/// This groups all records of a crate under one target. The `clog_*` family exists for every level.
+#[macro_export]
+macro_rules! clog_info {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
+    ($($arg:tt)*) => ({
+        $crate::log::info!(target: env!("CARGO_PKG_NAME"), "{}\n", format_args!($($arg)*));
+    })
+}
+
+#[macro_export]
+macro_rules! clog_error {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
+    ($($arg:tt)*) => ({
+        $crate::log::error!(target: env!("CARGO_PKG_NAME"), "{}\n", format_args!($($arg)*));
+    })
+}
+
+#[macro_export]
+macro_rules! clog_warn {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
+    ($($arg:tt)*) => ({
+        $crate::log::warn!(target: env!("CARGO_PKG_NAME"), "{}\n", format_args!($($arg)*));
+    })
+}
+
+#[macro_export]
+macro_rules! clog_debug {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
+    ($($arg:tt)*) => ({
+        $crate::log::debug!(target: env!("CARGO_PKG_NAME"), "{}\n", format_args!($($arg)*));
+    })
+}
+
+#[macro_export]
+macro_rules! clog_trace {
+    () => {
+        compile_error!("a log message is required, log an empty one explicitly with \"\"")
+    };
+    ($($arg:tt)*) => ({
+        $crate::log::trace!(target: env!("CARGO_PKG_NAME"), "{}\n", format_args!($($arg)*));
+    })
+}
+
+/// Check whether a level is enabled for the current module, before building an expensive message
+///
+/// # Example
+/// ```no_run
+/// use rimplog::enabled;
+///
+/// if enabled!(log::Level::Debug) {
+///     // build and log an expensive message
+/// }
+/// ```
+#[macro_export]
+macro_rules! enabled {
+    ($level:expr) => {
+        $crate::log::log_enabled!(target: module_path!(), $level)
+    };
+}
+
+/// Like `log_info!`, but located at the caller of the enclosing `#[track_caller]` function
+///
+/// Helpers logging on behalf of their callers can show where they were called from instead
+/// of their own line. Outside of a `#[track_caller]` function, the location is the macro call.
+/// The `log_*_caller` family exists for every level.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_warn_caller;
+///
+/// #[track_caller]
+/// fn deprecated(name: &str) {
+///     log_warn_caller!("{} is deprecated", name);
+/// }
+///
+/// deprecated("load_v1"); // logged with this line
+/// ```
+#[macro_export]
+macro_rules! log_info_caller {
+    ($($arg:tt)+) => ({
+        $crate::__log_caller(
+            $crate::log::Level::Info,
+            module_path!(),
+            ::core::panic::Location::caller(),
+            format_args!("{}\n", format_args!($($arg)+)),
+        );
+    })
+}
+
+#[macro_export]
+macro_rules! log_error_caller {
+    ($($arg:tt)+) => ({
+        $crate::__log_caller(
+            $crate::log::Level::Error,
+            module_path!(),
+            ::core::panic::Location::caller(),
+            format_args!("{}\n", format_args!($($arg)+)),
+        );
+    })
+}
+
+#[macro_export]
+macro_rules! log_warn_caller {
+    ($($arg:tt)+) => ({
+        $crate::__log_caller(
+            $crate::log::Level::Warn,
+            module_path!(),
+            ::core::panic::Location::caller(),
+            format_args!("{}\n", format_args!($($arg)+)),
+        );
+    })
+}
+
+#[macro_export]
+macro_rules! log_debug_caller {
+    ($($arg:tt)+) => ({
+        $crate::__log_caller(
+            $crate::log::Level::Debug,
+            module_path!(),
+            ::core::panic::Location::caller(),
+            format_args!("{}\n", format_args!($($arg)+)),
+        );
+    })
+}
+
+#[macro_export]
+macro_rules! log_trace_caller {
+    ($($arg:tt)+) => ({
+        $crate::__log_caller(
+            $crate::log::Level::Trace,
+            module_path!(),
+            ::core::panic::Location::caller(),
+            format_args!("{}\n", format_args!($($arg)+)),
+        );
+    })
+}
+
+/// Like `log_info!`, but with an explicit timestamp instead of the current time, for replaying events
+///
+/// The time is any `chrono` date time with a known offset, such as a `DateTime<Utc>` parsed from
+/// the event. The `log_*_at_time` family exists for every level.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_info_at_time;
+///
+/// let time = chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap();
+/// log_info_at_time!(time, "order {} shipped", 42);
+/// ```
+#[macro_export]
+macro_rules! log_info_at_time {
+    ($time:expr, $($arg:tt)+) => ({
+        $crate::__log_at_time($time, || $crate::log_info!($($arg)+));
+    })
+}
+
+#[macro_export]
+macro_rules! log_error_at_time {
+    ($time:expr, $($arg:tt)+) => ({
+        $crate::__log_at_time($time, || $crate::log_error!($($arg)+));
+    })
+}
+
+#[macro_export]
+macro_rules! log_warn_at_time {
+    ($time:expr, $($arg:tt)+) => ({
+        $crate::__log_at_time($time, || $crate::log_warn!($($arg)+));
+    })
+}
+
+#[macro_export]
+macro_rules! log_debug_at_time {
+    ($time:expr, $($arg:tt)+) => ({
+        $crate::__log_at_time($time, || $crate::log_debug!($($arg)+));
+    })
+}
+
+#[macro_export]
+macro_rules! log_trace_at_time {
+    ($time:expr, $($arg:tt)+) => ({
+        $crate::__log_at_time($time, || $crate::log_trace!($($arg)+));
+    })
+}
+
+/// Like `log_info!`, but only logs with probability `rate` between 0 and 1, for hot spots
+///
+/// Every call site samples on its own with a fast thread-local generator, independently of
+/// `burst_policy`. The `log_*_sampled` family exists for every level.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_info_sampled;
+///
+/// for request in 0..10_000 {
+///     // Logs about one request in ten
+///     log_info_sampled!(0.1, "handling request {}", request);
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_info_sampled {
+    ($rate:expr, $($arg:tt)+) => ({
+        if $crate::__sample($rate) {
+            $crate::log_info!($($arg)+);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_error_sampled {
+    ($rate:expr, $($arg:tt)+) => ({
+        if $crate::__sample($rate) {
+            $crate::log_error!($($arg)+);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_warn_sampled {
+    ($rate:expr, $($arg:tt)+) => ({
+        if $crate::__sample($rate) {
+            $crate::log_warn!($($arg)+);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_debug_sampled {
+    ($rate:expr, $($arg:tt)+) => ({
+        if $crate::__sample($rate) {
+            $crate::log_debug!($($arg)+);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_trace_sampled {
+    ($rate:expr, $($arg:tt)+) => ({
+        if $crate::__sample($rate) {
+            $crate::log_trace!($($arg)+);
+        }
+    })
+}
+
+/// Like `log_debug!`, but takes a closure building the message that is only called if debug is enabled
+///
+/// The `log_*_lazy` family exists for every level.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_debug_lazy;
+///
+/// let items = vec![1, 2, 3];
+/// log_debug_lazy!(|| format!("items: {:?}", items));
+/// ```
+#[macro_export]
+macro_rules! log_debug_lazy {
+    ($message:expr) => ({
+        if $crate::enabled!($crate::log::Level::Debug) {
+            $crate::log_debug!("{}", ($message)());
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_info_lazy {
+    ($message:expr) => ({
+        if $crate::enabled!($crate::log::Level::Info) {
+            $crate::log_info!("{}", ($message)());
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_error_lazy {
+    ($message:expr) => ({
+        if $crate::enabled!($crate::log::Level::Error) {
+            $crate::log_error!("{}", ($message)());
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_warn_lazy {
+    ($message:expr) => ({
+        if $crate::enabled!($crate::log::Level::Warn) {
+            $crate::log_warn!("{}", ($message)());
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log_trace_lazy {
+    ($message:expr) => ({
+        if $crate::enabled!($crate::log::Level::Trace) {
+            $crate::log_trace!("{}", ($message)());
+        }
+    })
+}
+
+/// Log the error of a `Result` and evaluate to the `Result` unchanged
+///
+/// The level defaults to `Error` when omitted. The record's target is the calling module, unlike
+/// [`LogErrExt::log_err`] which uses the crate name.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_err;
+///
+/// let value = log_err!("42".parse::<u32>());
+/// let value = log_err!(log::Level::Warn, "42".parse::<u32>());
+/// ```
+#[macro_export]
+macro_rules! log_err {
+    ($result:expr) => {
+        $crate::log_err!($crate::log::Level::Error, $result)
+    };
+    ($level:expr, $result:expr) => ({
+        let result = $result;
+        if let ::std::result::Result::Err(ref err) = result {
+            $crate::log::log!(target: module_path!(), $level, "{}\n", err);
+        }
+        result
+    })
+}
+
+/// Log an error instead of panicking when a condition is false, evaluates to the condition
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_assert;
+///
+/// let cache_size = 0;
+/// if !log_assert!(cache_size > 0, "cache is empty, falling back") {
+///     // degrade gracefully
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_assert {
+    ($cond:expr $(,)?) => ({
+        let passed: bool = $cond;
+        if !passed {
+            $crate::log_error!("assertion failed: {}", stringify!($cond));
+        }
+        passed
+    });
+    ($cond:expr, $($arg:tt)+) => ({
+        let passed: bool = $cond;
+        if !passed {
+            $crate::log_error!("assertion failed: {}: {}", stringify!($cond), format_args!($($arg)+));
+        }
+        passed
+    });
+}
+
+/// Like [`log_assert!`], but panics after logging the error
+#[macro_export]
+macro_rules! log_assert_panic {
+    ($cond:expr $(,)?) => ({
+        if !$cond {
+            $crate::log_error!("assertion failed: {}", stringify!($cond));
+            panic!("assertion failed: {}", stringify!($cond));
+        }
+    });
+    ($cond:expr, $($arg:tt)+) => ({
+        if !$cond {
+            $crate::log_error!("assertion failed: {}: {}", stringify!($cond), format_args!($($arg)+));
+            panic!("assertion failed: {}: {}", stringify!($cond), format_args!($($arg)+));
+        }
+    });
+}
+
+/// Log bytes as an offset, hex and ASCII dump at `level`, such as a protocol frame
+///
+/// The dump is only built when `level` is enabled, and shows at most [`HEXDUMP_MAX_BYTES`]
+/// bytes unless another cap is given as the third argument.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_hexdump;
+///
+/// let frame = [0x48, 0x65, 0x6c, 0x6c, 0x6f];
+/// log_hexdump!(log::Level::Debug, &frame);
+/// log_hexdump!(log::Level::Trace, &frame, 64);
+/// ```
+#[macro_export]
+macro_rules! log_hexdump {
+    ($level:expr, $bytes:expr $(,)?) => {
+        $crate::log_hexdump!($level, $bytes, $crate::HEXDUMP_MAX_BYTES)
+    };
+    ($level:expr, $bytes:expr, $max_bytes:expr $(,)?) => ({
+        let level = $level;
+        if $crate::log::log_enabled!(target: module_path!(), level) {
+            let bytes = &$bytes;
+            let bytes: &[u8] = ::core::convert::AsRef::as_ref(bytes);
+            $crate::log::log!(target: module_path!(), level, "{}", $crate::__hexdump(bytes, $max_bytes));
+        }
+    });
+}
+
+/// Time a block doing `count` operations, and log its throughput at info level
+///
+/// Logs a line such as `parse: 1000 ops in 12.5ms (80000/s)` and evaluates to the block's value.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_throughput;
+///
+/// let lines = vec!["a=1"; 1000];
+/// let parsed = log_throughput!("parse", lines.len(), {
+///     lines.iter().map(|line| line.split_once('=')).collect::<Vec<_>>()
+/// });
+/// ```
+#[macro_export]
+macro_rules! log_throughput {
+    ($label:expr, $count:expr, $body:block) => {{
+        let start = ::std::time::Instant::now();
+        let value = $body;
+        let elapsed = start.elapsed();
+        let count = $count;
+        $crate::log_info!(
+            "{}: {} ops in {:?} ({:.0}/s)",
+            $label,
+            count,
+            elapsed,
+            count as f64 / elapsed.as_secs_f64()
+        );
+        value
+    }};
+}
+
+/// Like `dbg!`, but logs the expressions and their values at debug level through the logger
+///
+/// Evaluates to the value for a single expression, and to a tuple of the values otherwise.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_dbg;
+///
+/// let width = 3;
+/// let height = 4;
+/// let area = log_dbg!(width * height);
+/// let (width, height) = log_dbg!(width, height);
+/// ```
+#[macro_export]
+macro_rules! log_dbg {
+    ($value:expr $(,)?) => {
+        match $value {
+            value => {
+                $crate::log_debug!("{} = {:?}", stringify!($value), &value);
+                value
+            }
+        }
+    };
+    ($($value:expr),+ $(,)?) => {
+        $crate::log_dbg!(@bind [] $($value,)+)
+    };
+    // Bind each value to its own `value`, which hygiene keeps apart across the recursion
+    (@bind [$($bound:tt)*] $head:expr, $($rest:expr,)*) => {
+        match $head {
+            value => $crate::log_dbg!(@bind [$($bound)* (value, $head)] $($rest,)*),
+        }
+    };
+    (@bind [$(($bound:ident, $source:expr))*]) => {{
+        $crate::log_debug!(
+            "{}",
+            [$(format!("{} = {:?}", stringify!($source), &$bound)),*].join(", ")
+        );
+        ($($bound),*)
+    }};
+}
+
+/// Render an error followed by its `source()` chain, one indented cause per line, used by [`log_error_chain!`]
+#[cfg(feature = "error-chain")]
+#[doc(hidden)]
+pub fn __error_chain<E: std::error::Error + ?Sized>(err: &E) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        chain.push_str("\n  caused by: ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
+
+/// Log an error at error level together with every cause of its `source()` chain
+///
+/// For `anyhow::Error`, pass `&*err` or `err.as_ref()`.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::log_error_chain;
+///
+/// if let Err(err) = std::fs::read_to_string("config.toml") {
+///     log_error_chain!(err);
+/// }
+/// ```
+#[cfg(feature = "error-chain")]
+#[macro_export]
+macro_rules! log_error_chain {
+    ($err:expr) => ({
+        $crate::log_error!("{}", $crate::__error_chain(&$err));
+    })
+}
+
+/// Log to a [`NamedLogger`] (or any `log::Log`) instead of the global logger
+///
+/// # Example
+/// ```no_run
+/// use rimplog::{log_scoped, LoggerBuilder, NamedLogger};
+///
+/// let plugin = NamedLogger::new("plugin", LoggerBuilder::default());
+/// log_scoped!(plugin, log::Level::Info, "loaded {} handlers", 3);
+/// ```
+#[macro_export]
+macro_rules! log_scoped {
+    ($scope:expr, $level:expr, $($arg:tt)*) => ({
+        $crate::__log_to(&$scope, $level, module_path!(), file!(), line!(), format_args!("{}\n", format_args!($($arg)*)));
+    })
+}
+
+/// Enter a span that lasts until the returned guard is dropped
+///
+/// Logs `-> name` on enter and `<- name` on exit at debug level, and indents
+/// messages logged in between on the same thread.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::span;
+///
+/// fn load() {
+///     let _span = span!("load");
+///     rimplog::debug!("reading config");
+/// }
+/// ```
+#[macro_export]
+macro_rules! span {
+    ($name:expr) => {
+        $crate::SpanGuard::enter($name, module_path!(), file!(), line!())
+    };
+}
+
+/// Run a block with every record logged on the current thread tagged with a correlation id
+///
+/// The id is shown as `[#id]` before the message, scopes can be nested and the block's value
+/// is returned. Other threads, such as spawned tasks, aren't tagged.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::{new_correlation_id, with_correlation};
+///
+/// fn handle_request() {
+///     with_correlation!(new_correlation_id(), {
+///         rimplog::info!("request started");
+///         rimplog::info!("request done");
+///     });
+/// }
+/// ```
+#[macro_export]
+macro_rules! with_correlation {
+    ($id:expr, $body:block) => {{
+        let _correlation = $crate::CorrelationGuard::enter($id);
+        $body
+    }};
+}
+
 pub use log_info as info;
 pub use log_error as error;
 pub use log_warn as warn;
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use log::{Level, Metadata, Record};
+
+/// A line built from fragments and logged as one record, see [`log_line!`](crate::log_line)
+///
+/// Fragments logged with the `_log_*` macros are separate records, so other threads
+/// can log between them and split the line. A `LogLine` keeps the fragments until it
+/// is dropped, then logs them with a trailing newline in a single record.
+pub struct LogLine {
+    level: Level,
+    target: &'static str,
+    file: &'static str,
+    line: u32,
+    message: String,
+}
+
+impl LogLine {
+    /// Start an empty line logged at `level`, use the [`log_line!`](crate::log_line)
+    /// macro instead of calling this directly
+    pub fn new(level: Level, target: &'static str, file: &'static str, line: u32) -> Self {
+        LogLine {
+            level,
+            target,
+            file,
+            line,
+            message: String::new(),
+        }
+    }
+
+    /// Append a fragment to the line
+    pub fn push(&mut self, fragment: fmt::Arguments) -> &mut Self {
+        if self.enabled() {
+            let _ = fmt::Write::write_fmt(&mut self.message, fragment);
+        }
+        self
+    }
+
+    /// Log the line now, instead of when it is dropped
+    pub fn finish(self) {}
+
+    fn metadata(&self) -> Metadata<'static> {
+        Metadata::builder().level(self.level).target(self.target).build()
+    }
+
+    /// Whether the line would be logged, so disabled lines don't collect their fragments
+    fn enabled(&self) -> bool {
+        self.level <= log::max_level() && log::logger().enabled(&self.metadata())
+    }
+}
+
+impl fmt::Write for LogLine {
+    fn write_str(&mut self, fragment: &str) -> fmt::Result {
+        if self.enabled() {
+            self.message.push_str(fragment);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        if !self.enabled() {
+            return;
+        }
+        if !self.message.ends_with('\n') {
+            self.message.push('\n');
+        }
+        log::logger().log(
+            &Record::builder()
+                .metadata(self.metadata())
+                .args(format_args!("{}", self.message))
+                .module_path_static(Some(self.target))
+                .file_static(Some(self.file))
+                .line(Some(self.line))
+                .build(),
+        );
+    }
+}
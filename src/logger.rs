@@ -0,0 +1,586 @@
+use env_logger::fmt::WriteStyle;
+use env_logger::{Builder, Env, Target};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::burst::BurstSampler;
+use crate::console::StdoutSink;
+use crate::format::{strip_ansi, RecordFormatter};
+use crate::network::NetworkWriter;
+use crate::sink::Sink;
+use crate::{ColorMode, LevelSource};
+
+/// How often `watch_level_file` is checked for changes
+const LEVEL_FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static LOGGER: OnceLock<RimpLogger> = OnceLock::new();
+/// Whether `LOGGER` was installed as the global logger
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Everything needed to (re)build the env_logger logger
+pub(crate) struct Backend {
+    pub(crate) formatter: Arc<RecordFormatter>,
+    /// Formats the lines of `target_files` and `network_sink` when they differ from the console, see `prettify_json_messages`
+    pub(crate) plain_formatter: Option<Arc<RecordFormatter>>,
+    pub(crate) only_project_logs: bool,
+    pub(crate) project_name: String,
+    pub(crate) file: Option<File>,
+    /// Whether `file` couldn't be opened, records then go to stdout
+    pub(crate) file_fallback: bool,
+    /// The original stderr when `capture_stderr` redirected file descriptor 2
+    pub(crate) stderr: Option<File>,
+    /// Files receiving the records whose target starts with their prefix, locked so lines are written whole
+    pub(crate) target_files: Arc<Vec<(String, Mutex<File>)>>,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) network: Option<Arc<NetworkWriter>>,
+    /// The stdout of `split_streams`
+    pub(crate) stdout: Arc<StdoutSink>,
+    /// The stdout of `SinkTarget::Stdout` sinks and of the `file` fallback, the same as `stdout` when
+    /// `stdout_buffering` is set and line-buffered otherwise
+    pub(crate) sink_stdout: Arc<StdoutSink>,
+    /// `sinks` and `debug_file`, with levels of their own
+    pub(crate) sinks: Vec<Sink>,
+    pub(crate) burst: Option<BurstSampler>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Option<crate::MetricsConfig>,
+    #[cfg(feature = "gelf")]
+    pub(crate) gelf: Option<crate::gelf::GelfWriter>,
+}
+
+impl Backend {
+    /// Build an env_logger logger using `level` as the default level
+    fn build(&self, level: LevelFilter) -> env_logger::Logger {
+        let formatter = Arc::clone(&self.formatter);
+        let level_source = formatter.config().level_source;
+        let mut builder = filtered_builder(level_source, level, self.only_project_logs, &self.project_name);
+
+        // Binary records must reach the sink untouched by escape stripping
+        if formatter.is_binary() {
+            builder.write_style(WriteStyle::Always);
+        } else {
+            match self.color_mode {
+                ColorMode::Auto => {}
+                ColorMode::Always => {
+                    builder.write_style(WriteStyle::Always);
+                }
+                ColorMode::Never => {
+                    builder.write_style(WriteStyle::Never);
+                }
+            }
+        }
+
+        let target_files = Arc::clone(&self.target_files);
+        let network = self.network.clone();
+        let plain_formatter = self.plain_formatter.clone();
+        // Records go to stdout when `file` couldn't be opened
+        let stdout = Arc::clone(if self.file_fallback { &self.sink_stdout } else { &self.stdout });
+        let config = formatter.config();
+        let split_streams = config.split_streams && self.file.is_none();
+        let file_fallback = self.file_fallback;
+        let stderr_level = config.stderr_level;
+        let stdout_colors = formatter.is_binary()
+            || match self.color_mode {
+                ColorMode::Auto => io::stdout().is_terminal(),
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+            };
+        builder.format(move |buf, record| {
+            let routed = target_files.iter().find(|(prefix, _)| record.target().starts_with(prefix.as_str()));
+            // Records less severe than `stderr_level` go to stdout when splitting the console streams
+            let to_stdout = file_fallback || (split_streams && record.level() > stderr_level);
+            if routed.is_none() && !to_stdout && network.is_none() {
+                return formatter.write_record(buf, record);
+            }
+
+            // Format once for all sinks, so `collapse_consecutive` sees each record once
+            let mut bytes = Vec::new();
+            formatter.write_record(&mut bytes, record)?;
+            let plain_bytes = match &plain_formatter {
+                Some(plain_formatter) => {
+                    let mut plain_bytes = Vec::new();
+                    plain_formatter.write_record(&mut plain_bytes, record)?;
+                    Some(plain_bytes)
+                }
+                None => None,
+            };
+            let plain_bytes = plain_bytes.as_deref().unwrap_or(&bytes);
+            if let Some(network) = &network {
+                if formatter.is_binary() {
+                    network.write(plain_bytes);
+                } else if !plain_bytes.is_empty() {
+                    network.write(strip_ansi(&String::from_utf8_lossy(plain_bytes)).as_bytes());
+                }
+            }
+            if let Some((_, file)) = routed {
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = if formatter.is_binary() {
+                    file.write_all(plain_bytes)
+                } else {
+                    file.write_all(strip_ansi(&String::from_utf8_lossy(plain_bytes)).as_bytes())
+                };
+                if formatter.config().target_files_only {
+                    return Ok(());
+                }
+            }
+            if to_stdout {
+                return if stdout_colors {
+                    stdout.write(&bytes)
+                } else {
+                    stdout.write(strip_ansi(&String::from_utf8_lossy(&bytes)).as_bytes())
+                };
+            }
+            buf.write_all(&bytes)
+        });
+
+        if let Some(file) = &self.file {
+            match file.try_clone() {
+                Ok(file) => {
+                    builder.target(Target::Pipe(Box::new(file)));
+                }
+                Err(err) => {
+                    eprintln!("Failed to reuse log file: {}, logging to stdout instead", err);
+                    builder.target(Target::Stdout);
+                }
+            }
+        } else if self.file_fallback {
+            builder.target(Target::Stdout);
+        } else if let Some(stderr) = &self.stderr {
+            match stderr.try_clone() {
+                Ok(stderr) => {
+                    // env_logger can't detect a terminal behind a pipe target
+                    if self.color_mode == ColorMode::Auto && !self.formatter.is_binary() {
+                        builder.write_style(if stderr.is_terminal() { WriteStyle::Always } else { WriteStyle::Never });
+                    }
+                    builder.target(Target::Pipe(Box::new(stderr)));
+                }
+                Err(err) => {
+                    eprintln!("Failed to reuse the original stderr: {}, its lines may be logged again", err);
+                }
+            }
+        }
+
+        builder.build()
+    }
+}
+
+/// Create an env_logger builder filtering records by `level` combined with `RUST_LOG`
+pub(crate) fn filtered_builder(
+    level_source: LevelSource,
+    level: LevelFilter,
+    only_project_logs: bool,
+    project_name: &str,
+) -> Builder {
+    let mut builder = match level_source {
+        LevelSource::BuilderOnly => Builder::new(),
+        LevelSource::EnvThenBuilder | LevelSource::EnvOnly => {
+            Builder::from_env(Env::default().filter_or("RUST_LOG", level.to_string()))
+        }
+    };
+    if only_project_logs {
+        builder
+            .filter(None, LevelFilter::Off)
+            .filter(Some(project_name), level);
+    } else if level_source != LevelSource::EnvOnly {
+        // Replaces the default level of `RUST_LOG`, keeping its module directives
+        builder.filter(None, level);
+    }
+    builder
+}
+
+/// The installed logger, an env_logger logger that is rebuilt when the level changes
+pub(crate) struct RimpLogger {
+    /// Cloned by every call rather than kept locked, so mirrors and callbacks that log again or
+    /// reconfigure can't deadlock with a waiting writer
+    active: RwLock<Arc<Active>>,
+    /// Records written at each level, from error to trace, for `exit_summary`
+    counts: [AtomicU64; 5],
+    started: Instant,
+}
+
+/// A backend and the env_logger logger built from it, swapped together by `reconfigure`
+struct Active {
+    backend: Backend,
+    /// Only locked while records are filtered or written, never around callbacks
+    inner: RwLock<env_logger::Logger>,
+}
+
+impl RimpLogger {
+    pub(crate) fn new(backend: Backend, level: LevelFilter) -> Self {
+        let inner = backend.build(level);
+        RimpLogger {
+            active: RwLock::new(Arc::new(Active {
+                backend,
+                inner: RwLock::new(inner),
+            })),
+            counts: Default::default(),
+            started: Instant::now(),
+        }
+    }
+
+    fn active(&self) -> Arc<Active> {
+        Arc::clone(&self.active.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replace the default level, `RUST_LOG` module directives are kept
+    pub(crate) fn set_level(&self, level: LevelFilter) {
+        let active = self.active();
+        let inner = active.backend.build(level);
+        *active.inner.write().unwrap_or_else(|e| e.into_inner()) = inner;
+        log::set_max_level(self.filter());
+    }
+
+    /// Set the colors and write the session separator and `file_header`, once for a new logger
+    ///
+    /// Unlike [`build`](crate::build), this isn't done again when [`replace`](Self::replace) switches to
+    /// another configuration.
+    pub(crate) fn start(&self) {
+        crate::console::apply_color_override(self.color_mode());
+        self.write_file_header();
+    }
+
+    /// The colors of the console, as decided from `color` and the environment
+    pub(crate) fn color_mode(&self) -> ColorMode {
+        self.active().backend.color_mode
+    }
+
+    /// Write the session separator and `file_header` to `file`
+    pub(crate) fn write_file_header(&self) {
+        let active = self.active();
+        let config = active.backend.formatter.config();
+        let Some(mut file) = active.backend.file.as_ref() else {
+            return;
+        };
+        // Lines of text would corrupt binary records and JSON lines
+        if config.preset.is_binary() || config.preset.is_json() {
+            return;
+        }
+        // Mark where this run starts when appending to a file with earlier runs
+        let has_earlier_runs = file.metadata().map(|metadata| metadata.len() > 0).unwrap_or(false);
+        if config.session_separator && has_earlier_runs {
+            let _ = writeln!(
+                file,
+                "===== session started {} (pid {}) =====",
+                chrono::Local::now().format(&config.time_format),
+                std::process::id()
+            );
+        }
+        if let Some(header) = &config.file_header {
+            let _ = writeln!(file, "{}", crate::render_file_template(header, &config.time_format));
+        }
+    }
+
+    /// Flush the current configuration, write its `file_footer` and switch to the one of `logger`, for `reconfigure`
+    ///
+    /// Records being logged on other threads finish with the previous configuration.
+    pub(crate) fn replace(&self, logger: RimpLogger) {
+        let replacement = logger.active.into_inner().unwrap_or_else(|e| e.into_inner());
+        let color_mode = replacement.backend.color_mode;
+        let mut active = self.active.write().unwrap_or_else(|e| e.into_inner());
+        active.finish();
+        let previous = std::mem::replace(&mut *active, replacement);
+        // The colors of the previous configuration mustn't outlive it
+        crate::console::apply_color_override(color_mode);
+        drop(active);
+        // Closes the files and sockets of the previous configuration, once no record uses them
+        drop(previous);
+        log::set_max_level(self.filter());
+    }
+
+    /// The original stderr when `capture_stderr` redirected it, which a new configuration must keep writing to
+    pub(crate) fn original_stderr(&self) -> Option<File> {
+        self.active().backend.stderr.as_ref().and_then(|stderr| stderr.try_clone().ok())
+    }
+
+    /// Log the `exit_summary` line, called when the [`FlushGuard`](crate::FlushGuard) is dropped
+    pub(crate) fn log_exit_summary(&self) {
+        let Some(template) = self.active().backend.formatter.config().exit_summary.clone() else {
+            return;
+        };
+        let count = |level: Level| self.counts[level as usize - 1].load(Ordering::Relaxed).to_string();
+        let summary = template
+            .replace("{errors}", &count(Level::Error))
+            .replace("{warnings}", &count(Level::Warn))
+            .replace("{infos}", &count(Level::Info))
+            .replace("{debugs}", &count(Level::Debug))
+            .replace("{traces}", &count(Level::Trace))
+            .replace("{duration}", &format!("{:.1}s", self.started.elapsed().as_secs_f64()));
+        crate::log_record(Level::Info, crate::project_name(), file!(), line!(), format_args!("{}\n", summary));
+    }
+
+    /// Flush and write `file_footer`, called when the [`FlushGuard`](crate::FlushGuard) is dropped
+    pub(crate) fn finish(&self) {
+        self.active().finish();
+    }
+
+    /// Get the most verbose level reaching any sink
+    pub(crate) fn filter(&self) -> LevelFilter {
+        let active = self.active();
+        let filter = active.inner().filter();
+        let filter = active.backend.sinks.iter().map(|sink| sink.level).fold(filter, Ord::max);
+        let config = active.backend.formatter.config();
+        let filter = match config.max_allowed_level {
+            Some(max_allowed_level) => filter.min(max_allowed_level),
+            None => filter,
+        };
+        remapped_filter(filter, &config.level_remap)
+    }
+}
+
+/// Raise `filter` to the levels that `level_remap` raises to a level it passes, so the facade lets them through
+pub(crate) fn remapped_filter(filter: LevelFilter, level_remap: &[(String, Level, Level)]) -> LevelFilter {
+    level_remap
+        .iter()
+        .filter(|(_, _, to)| *to <= filter)
+        .map(|(_, from, _)| from.to_level_filter())
+        .fold(filter, Ord::max)
+}
+
+impl Active {
+    fn inner(&self) -> RwLockReadGuard<'_, env_logger::Logger> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the level of a record with `level_remap` applied
+    fn remapped_level(&self, target: &str, level: Level) -> Level {
+        self.backend
+            .formatter
+            .config()
+            .level_remap
+            .iter()
+            .find(|(prefix, from, _)| *from == level && target.starts_with(prefix.as_str()))
+            .map_or(level, |(_, _, to)| *to)
+    }
+
+    /// Whether `level` is within `max_allowed_level`
+    fn allowed(&self, level: Level) -> bool {
+        self.backend.formatter.config().max_allowed_level.is_none_or(|max| level <= max)
+    }
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if !self.backend.formatter.logs_target(metadata.target()) {
+            return false;
+        }
+        let level = self.remapped_level(metadata.target(), metadata.level());
+        if !self.allowed(level) {
+            return false;
+        }
+        self.reaches_a_sink(&Metadata::builder().level(level).target(metadata.target()).build())
+    }
+
+    /// Whether a record with `metadata`, already remapped, is written to the console or a sink
+    fn reaches_a_sink(&self, metadata: &Metadata) -> bool {
+        self.backend.sinks.iter().any(|sink| metadata.level() <= sink.level) || self.inner().enabled(metadata)
+    }
+
+    /// Write a record, returning the level it was written at if any sink took it
+    fn log(&self, record: &Record) -> Option<Level> {
+        if !self.backend.formatter.logs_target(record.target()) {
+            return None;
+        }
+        let level = self.remapped_level(record.target(), record.level());
+        if !self.allowed(level) {
+            return None;
+        }
+        let remapped;
+        let record = if level == record.level() {
+            record
+        } else {
+            let mut builder = Record::builder();
+            builder
+                .metadata(Metadata::builder().level(level).target(record.target()).build())
+                .args(*record.args())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line());
+            #[cfg(feature = "kv")]
+            builder.key_values(record.key_values());
+            remapped = builder.build();
+            &remapped
+        };
+        if let Some(burst) = &self.backend.burst {
+            // Only count the records that would be written
+            if !self.reaches_a_sink(record.metadata()) || !burst.admit(record.target()) {
+                return None;
+            }
+        }
+        // Released before the callbacks below, which may log again
+        let matches = self.inner().matches(record);
+        let written = matches || self.backend.sinks.iter().any(|sink| level <= sink.level);
+        if let Some(mirror) = &self.backend.formatter.config().record_mirror {
+            if written {
+                mirror(record);
+            }
+        }
+        #[cfg(feature = "tracing-mirror")]
+        if written && self.backend.formatter.config().tracing_mirror {
+            crate::tracing_mirror::emit(record, &self.backend.formatter.message(record));
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.backend.metrics {
+            if matches {
+                metrics.count(record);
+            }
+        }
+        #[cfg(feature = "sentry")]
+        if let Some(sentry) = &self.backend.formatter.config().sentry {
+            if matches {
+                sentry.report(record, &self.backend.formatter.message(record));
+            }
+        }
+        if let Some(sender) = &self.backend.formatter.config().channel_sink {
+            if matches {
+                let formatter = &self.backend.formatter;
+                let timestamp = crate::clock::record_time(&*formatter.config().clock);
+                crate::event::send(sender, record, &formatter.message(record), timestamp);
+            }
+        }
+        #[cfg(feature = "gelf")]
+        if let Some(gelf) = &self.backend.gelf {
+            if matches {
+                let formatter = &self.backend.formatter;
+                gelf.write(record, &formatter.message(record), crate::clock::record_time(&*formatter.config().clock));
+            }
+        }
+        let config = self.backend.formatter.config();
+        // Sinks may write to the console too, so they are written while the bars are hidden
+        let mut write = || {
+            for sink in &self.backend.sinks {
+                sink.write(record);
+            }
+            self.inner().log(record);
+        };
+        match &config.progress_suspend {
+            // Only written records, since the bars are drawn again after each call
+            Some(suspend) if written => suspend(&mut write),
+            _ => write(),
+        }
+
+        if let (true, Some(redraw)) = (config.progress_aware, &config.progress_redraw) {
+            if matches {
+                redraw();
+            }
+        }
+        written.then_some(level)
+    }
+
+    fn flush(&self) {
+        self.inner().flush();
+        self.backend.stdout.flush();
+        self.backend.sink_stdout.flush();
+        for sink in &self.backend.sinks {
+            sink.flush();
+        }
+        if let Some(network) = &self.backend.network {
+            network.flush();
+        }
+    }
+
+    /// Flush and write `file_footer`, at the end of the program or of the configuration
+    fn finish(&self) {
+        self.flush();
+        let config = self.backend.formatter.config();
+        if let (Some(footer), Some(mut file)) = (&config.file_footer, self.backend.file.as_ref()) {
+            if !config.preset.is_binary() && !config.preset.is_json() {
+                let _ = writeln!(file, "{}", crate::render_file_template(footer, &config.time_format));
+            }
+        }
+    }
+}
+
+impl Log for RimpLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.active().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(level) = self.active().log(record) {
+            self.counts[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {
+        self.active().flush();
+    }
+}
+
+/// Install `logger` as the global logger
+pub(crate) fn install(logger: RimpLogger) -> Result<&'static RimpLogger, log::SetLoggerError> {
+    let logger = LOGGER.get_or_init(|| logger);
+    log::set_logger(logger)?;
+    // The facade drops records above its max level before they reach the logger, so it must be
+    // the most verbose of the default level and the `RUST_LOG` module directives
+    log::set_max_level(logger.filter());
+    INSTALLED.store(true, Ordering::Release);
+    Ok(logger)
+}
+
+/// Get the global logger, if rimplog installed it
+pub(crate) fn installed() -> Option<&'static RimpLogger> {
+    // `LOGGER` is also set when another logger was already installed
+    if INSTALLED.load(Ordering::Acquire) {
+        LOGGER.get()
+    } else {
+        None
+    }
+}
+
+/// Warn that `RUST_LOG` overrides `level`, for `warn_env_override`
+pub(crate) fn warn_env_override(level_source: LevelSource, level: LevelFilter) {
+    let Some(rust_log) = env::var("RUST_LOG").ok().filter(|rust_log| !rust_log.trim().is_empty()) else {
+        return;
+    };
+    match level_source {
+        LevelSource::BuilderOnly => {}
+        LevelSource::EnvThenBuilder if names_modules(&rust_log) => eprintln!(
+            "RUST_LOG='{}' is set and overrides level '{}' for the modules it names",
+            rust_log, level
+        ),
+        LevelSource::EnvThenBuilder => {}
+        LevelSource::EnvOnly => eprintln!("RUST_LOG='{}' is set and overrides level '{}'", rust_log, level),
+    }
+}
+
+/// Whether `RUST_LOG` has directives for modules, rather than only a default level
+fn names_modules(rust_log: &str) -> bool {
+    let directives = rust_log.split('/').next().unwrap_or("");
+    directives
+        .split(',')
+        .map(str::trim)
+        .any(|directive| !directive.is_empty() && directive.parse::<LevelFilter>().is_err())
+}
+
+/// Poll `path` for a level name and apply it whenever it changes
+pub(crate) fn watch_level_file(logger: &'static RimpLogger, path: PathBuf) {
+    let spawned = thread::Builder::new()
+        .name("rimplog-level-watch".to_string())
+        .spawn(move || {
+            let mut last_contents: Option<String> = None;
+            loop {
+                if let Ok(contents) = fs::read_to_string(&path) {
+                    let contents = contents.trim().to_lowercase();
+                    if last_contents.as_deref() != Some(contents.as_str()) {
+                        match contents.parse::<LevelFilter>() {
+                            Ok(level) => logger.set_level(level),
+                            Err(_) => eprintln!(
+                                "Invalid log level '{}' in '{}', keeping the current level",
+                                contents,
+                                path.display()
+                            ),
+                        }
+                        last_contents = Some(contents);
+                    }
+                }
+                thread::sleep(LEVEL_FILE_POLL_INTERVAL);
+            }
+        });
+    if let Err(err) = spawned {
+        eprintln!("Failed to watch log level file: {}", err);
+    }
+}
@@ -0,0 +1,49 @@
+use log::{Log, Metadata, Record};
+
+use crate::logger::RimpLogger;
+use crate::{ColorMode, LoggerBuilder};
+
+/// A logger that is not installed globally, for libraries and plugins that need
+/// their own configuration next to the application's logger
+///
+/// Log to it with [`log_scoped!`](crate::log_scoped). `watch_level_file` and
+/// `log_config_on_init` only apply to the global logger and are ignored here.
+pub struct NamedLogger {
+    name: String,
+    logger: RimpLogger,
+}
+
+impl NamedLogger {
+    /// Create a named logger from a logger builder
+    pub fn new(name: impl Into<String>, logger_builder: LoggerBuilder) -> Self {
+        let logger = crate::build(logger_builder, None).logger;
+        logger.write_file_header();
+        // Colors are global, leave them to the global logger unless they are forced
+        if logger.color_mode() == ColorMode::Always {
+            colored::control::set_override(true);
+        }
+        NamedLogger {
+            name: name.into(),
+            logger,
+        }
+    }
+
+    /// Get the name of the logger
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Log for NamedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.logger.log(record);
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
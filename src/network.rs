@@ -0,0 +1,228 @@
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Largest batch sent in one UDP datagram, below the 65507 bytes limit of IPv4
+const MAX_DATAGRAM_SIZE: usize = 60_000;
+
+/// A network destination receiving every line as plain text, in addition to the main sink
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkSink {
+    /// One datagram per line, or per batch
+    Udp(SocketAddr),
+    /// A connection that is reopened on the next write when it fails
+    Tcp(SocketAddr),
+    /// A connection to a Unix domain socket, such as a local aggregation daemon, reopened like `Tcp`
+    #[cfg(unix)]
+    UnixSocket(PathBuf),
+}
+
+/// A connected network sink
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+struct State {
+    connection: Option<Connection>,
+    /// Lines waiting to be sent as one batch
+    pending: Vec<u8>,
+    pending_lines: usize,
+    last_flush: Instant,
+}
+
+/// Writes lines to a [`NetworkSink`], optionally in batches
+pub(crate) struct NetworkWriter {
+    sink: NetworkSink,
+    batch: Option<(usize, Duration)>,
+    state: Mutex<State>,
+}
+
+impl NetworkWriter {
+    /// Create a writer, with a thread sending batches that reached their time threshold
+    pub(crate) fn new(sink: NetworkSink, batch: Option<(usize, Duration)>) -> Arc<Self> {
+        let writer = Arc::new(NetworkWriter {
+            sink,
+            batch,
+            state: Mutex::new(State {
+                connection: None,
+                pending: Vec::new(),
+                pending_lines: 0,
+                last_flush: Instant::now(),
+            }),
+        });
+
+        if let Some((_, interval)) = batch {
+            // Only keep a weak reference, so the thread stops when the logger is dropped
+            let weak = Arc::downgrade(&writer);
+            let spawned = thread::Builder::new()
+                .name("rimplog-batch-flush".to_string())
+                .spawn(move || loop {
+                    thread::sleep(interval);
+                    let Some(writer) = weak.upgrade() else {
+                        break;
+                    };
+                    let mut state = writer.state.lock().unwrap_or_else(|e| e.into_inner());
+                    if state.last_flush.elapsed() >= interval {
+                        writer.send_pending(&mut state);
+                    }
+                });
+            if let Err(err) = spawned {
+                eprintln!("Failed to start the network batch flush thread: {}", err);
+            }
+        }
+
+        writer
+    }
+
+    /// Send a line, or add it to the current batch
+    pub(crate) fn write(&self, line: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some((max_lines, interval)) = self.batch else {
+            let _ = self.send(&mut state.connection, line);
+            return;
+        };
+
+        if matches!(self.sink, NetworkSink::Udp(_)) && state.pending.len() + line.len() > MAX_DATAGRAM_SIZE {
+            self.send_pending(&mut state);
+        }
+        state.pending.extend_from_slice(line);
+        state.pending_lines += 1;
+        if state.pending_lines >= max_lines || state.last_flush.elapsed() >= interval {
+            self.send_pending(&mut state);
+        }
+    }
+
+    /// Send the current batch
+    pub(crate) fn flush(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.send_pending(&mut state);
+    }
+
+    fn send_pending(&self, state: &mut State) {
+        state.last_flush = Instant::now();
+        if state.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut state.pending);
+        state.pending_lines = 0;
+        let _ = self.send(&mut state.connection, &pending);
+    }
+
+    /// Send a payload, connecting first if needed and dropping the connection when it fails
+    fn send(&self, connection: &mut Option<Connection>, payload: &[u8]) -> io::Result<()> {
+        if connection.is_none() {
+            *connection = Some(match &self.sink {
+                NetworkSink::Udp(addr) => {
+                    let local: SocketAddr = if addr.is_ipv4() {
+                        ([0, 0, 0, 0], 0).into()
+                    } else {
+                        ([0u16; 8], 0).into()
+                    };
+                    let socket = UdpSocket::bind(local)?;
+                    socket.connect(addr)?;
+                    Connection::Udp(socket)
+                }
+                NetworkSink::Tcp(addr) => Connection::Tcp(TcpStream::connect(addr)?),
+                #[cfg(unix)]
+                NetworkSink::UnixSocket(path) => Connection::Unix(UnixStream::connect(path)?),
+            });
+        }
+        let result = match connection.as_mut() {
+            Some(Connection::Udp(socket)) => socket.send(payload).map(|_| ()),
+            Some(Connection::Tcp(stream)) => stream.write_all(payload),
+            #[cfg(unix)]
+            Some(Connection::Unix(stream)) => stream.write_all(payload),
+            None => Ok(()),
+        };
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A UDP socket receiving on a free local port
+    fn receiver() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let addr = socket.local_addr().unwrap();
+        (socket, addr)
+    }
+
+    fn receive(socket: &UdpSocket) -> String {
+        let mut buf = [0; 1024];
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+
+    #[test]
+    fn batched_lines_arrive_in_one_datagram() {
+        let (socket, addr) = receiver();
+        let writer = NetworkWriter::new(NetworkSink::Udp(addr), Some((3, Duration::from_secs(60))));
+        for line in ["one\n", "two\n", "three\n", "four\n"] {
+            writer.write(line.as_bytes());
+        }
+        assert_eq!(receive(&socket), "one\ntwo\nthree\n");
+        // The rest of a batch is sent on flush
+        writer.flush();
+        assert_eq!(receive(&socket), "four\n");
+    }
+
+    #[test]
+    fn unbatched_lines_arrive_one_by_one() {
+        let (socket, addr) = receiver();
+        let writer = NetworkWriter::new(NetworkSink::Udp(addr), None);
+        writer.write(b"one\n");
+        writer.write(b"two\n");
+        assert_eq!(receive(&socket), "one\n");
+        assert_eq!(receive(&socket), "two\n");
+    }
+
+    #[test]
+    fn batches_are_sent_after_the_interval() {
+        let (socket, addr) = receiver();
+        let writer = NetworkWriter::new(NetworkSink::Udp(addr), Some((100, Duration::from_millis(50))));
+        writer.write(b"one\n");
+        writer.write(b"two\n");
+        assert_eq!(receive(&socket), "one\ntwo\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_reconnects_after_a_failure() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("rimplog-{}-reconnect.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let read_line = || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).unwrap();
+            line
+        };
+        let writer = NetworkWriter::new(NetworkSink::UnixSocket(path.clone()), None);
+        writer.write(b"one\n");
+        // The connection is closed once the line is read
+        assert_eq!(read_line(), "one\n");
+        // Lost, but the failure drops the connection
+        writer.write(b"two\n");
+        writer.write(b"three\n");
+        assert_eq!(read_line(), "three\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{ColorTheme, ExternalDetail, LoggerBuilder, LoggerPreset, PathDepth, PathFallback};
+
+impl LoggerBuilder {
+    /// Parse a compact configuration such as `level=debug,preset=thread,depth=2`
+    ///
+    /// Keys are `level`, `preset`, `depth`, `time_format`, `only_project_logs`, `file`,
+    /// `path_fallback`, `external_detail` and `theme`. Unknown keys and invalid values
+    /// print a warning and are ignored. Values can't contain commas.
+    pub fn parse(config: &str) -> LoggerBuilder {
+        let mut builder = LoggerBuilder::default();
+        for entry in config.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let Some((key, value)) = entry.split_once('=') else {
+                eprintln!("Invalid logger config entry '{}', expected key=value", entry);
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let applied = match key {
+                "level" => {
+                    builder.level = value.to_string();
+                    true
+                }
+                "preset" => parse_preset(value).map(|preset| builder.preset = preset).is_some(),
+                "depth" | "path_depth" => parse_path_depth(value).map(|depth| builder.path_depth = depth).is_some(),
+                "time_format" => {
+                    builder.time_format = value.to_string();
+                    true
+                }
+                "only_project_logs" => value.parse().map(|only| builder.only_project_logs = only).is_ok(),
+                "file" => {
+                    builder.file = Some(value.into());
+                    true
+                }
+                "path_fallback" => parse_path_fallback(value).map(|fallback| builder.path_fallback = fallback).is_some(),
+                "external_detail" => parse_external_detail(value).map(|detail| builder.external_detail = detail).is_some(),
+                "theme" => parse_theme(value).map(|theme| builder.color_theme = theme).is_some(),
+                _ => {
+                    eprintln!("Unknown logger config key '{}', ignoring it", key);
+                    continue;
+                }
+            };
+            if !applied {
+                eprintln!("Invalid value '{}' for logger config key '{}', ignoring it", value, key);
+            }
+        }
+        builder
+    }
+}
+
+fn parse_preset(value: &str) -> Option<LoggerPreset> {
+    match value.to_lowercase().as_str() {
+        "full" => Some(LoggerPreset::FULL),
+        "thread" => Some(LoggerPreset::THREAD),
+        "simple" => Some(LoggerPreset::SIMPLE),
+        "raw" => Some(LoggerPreset::RAW),
+        #[cfg(feature = "binary")]
+        "binary" => Some(LoggerPreset::BINARY),
+        #[cfg(feature = "json")]
+        "json" => Some(LoggerPreset::JSON),
+        _ => None,
+    }
+}
+
+/// Parse `full`, `file` or a number of components, where `0` still means the full path
+fn parse_path_depth(value: &str) -> Option<PathDepth> {
+    match value.to_lowercase().as_str() {
+        "full" => Some(PathDepth::Full),
+        "file" | "file_only" => Some(PathDepth::FileOnly),
+        value => value.parse::<usize>().ok().map(PathDepth::from),
+    }
+}
+
+fn parse_path_fallback(value: &str) -> Option<PathFallback> {
+    match value.to_lowercase().as_str() {
+        "full" | "full_path" => Some(PathFallback::FullPath),
+        "file_name" | "file_name_only" => Some(PathFallback::FileNameOnly),
+        "as_is" => Some(PathFallback::AsIs),
+        _ => None,
+    }
+}
+
+fn parse_external_detail(value: &str) -> Option<ExternalDetail> {
+    match value.to_lowercase().as_str() {
+        "target_and_path" => Some(ExternalDetail::TargetAndPath),
+        "target_only" => Some(ExternalDetail::TargetOnly),
+        "hidden" => Some(ExternalDetail::Hidden),
+        _ => None,
+    }
+}
+
+fn parse_theme(value: &str) -> Option<ColorTheme> {
+    match value.to_lowercase().as_str() {
+        "default" => Some(ColorTheme::Default),
+        "solarized" => Some(ColorTheme::Solarized),
+        "monochrome" => Some(ColorTheme::Monochrome),
+        "high_contrast" => Some(ColorTheme::HighContrast),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn parse_applies_every_key() {
+        let builder = LoggerBuilder::parse(
+            "level=debug, preset=THREAD,depth=2,time_format=%H:%M,only_project_logs=true,file=app.log,\
+             path_fallback=file_name,external_detail=hidden,theme=high_contrast",
+        );
+        assert_eq!(builder.level, "debug");
+        assert_eq!(builder.preset, LoggerPreset::THREAD);
+        assert_eq!(builder.path_depth, PathDepth::Components(2));
+        assert_eq!(builder.time_format, "%H:%M");
+        assert!(builder.only_project_logs);
+        assert_eq!(builder.file, Some(PathBuf::from("app.log")));
+        assert_eq!(builder.path_fallback, PathFallback::FileNameOnly);
+        assert_eq!(builder.external_detail, ExternalDetail::Hidden);
+        assert_eq!(builder.color_theme, ColorTheme::HighContrast);
+        assert_eq!(LoggerBuilder::parse("depth=file").path_depth, PathDepth::FileOnly);
+        assert_eq!(LoggerBuilder::parse("depth=0").path_depth, PathDepth::Full);
+    }
+
+    #[test]
+    fn parse_ignores_malformed_entries() {
+        let defaults = LoggerBuilder::default();
+        let builder = LoggerBuilder::parse("verbose,colour=red,preset=fancy,depth=-1,only_project_logs=yes,,level=warn");
+        assert_eq!(builder.level, "warn");
+        assert_eq!(builder.preset, defaults.preset);
+        assert_eq!(builder.path_depth, defaults.path_depth);
+        assert_eq!(builder.only_project_logs, defaults.only_project_logs);
+
+        let builder = LoggerBuilder::parse("");
+        assert_eq!(builder.level, defaults.level);
+        assert_eq!(builder.file, None);
+    }
+}
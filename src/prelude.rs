@@ -0,0 +1,22 @@
+//! The items most programs need, to import with `use rimplog::prelude::*;`
+//!
+//! # Example
+//! ```
+//! use rimplog::prelude::*;
+//!
+//! init_logger(LoggerBuilder {
+//!     preset: LoggerPreset::THREAD,
+//!     level: "info".to_string(),
+//!     ..LoggerBuilder::default()
+//! });
+//! log_info!("ready");
+//! rimplog::set_max_level(LevelFilter::Debug);
+//! assert_eq!(rimplog::log::max_level(), LevelFilter::Debug);
+//! log_debug!("listening on port {}", 8080);
+//! ```
+
+pub use crate::log::{Level, LevelFilter};
+pub use crate::{flush, init_logger, init_logger_with_guard, try_init_logger};
+pub use crate::{ColorMode, FlushGuard, LoggerBuilder, LoggerPreset};
+pub use crate::{log_debug, log_error, log_info, log_trace, log_warn};
+pub use crate::{_log_debug, _log_error, _log_info, _log_trace, _log_warn};
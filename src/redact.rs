@@ -0,0 +1,62 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Rewrites a message before it is written, for example to mask sensitive data
+pub type Redactor = Arc<dyn for<'a> Fn(&'a str) -> Cow<'a, str> + Send + Sync>;
+
+/// Create a redactor replacing every match of `pattern` with `replacement`
+///
+/// `replacement` can refer to capture groups such as `$1`.
+#[cfg(feature = "redact")]
+pub fn regex_redactor(pattern: &str, replacement: &str) -> Result<Redactor, regex::Error> {
+    let regex = regex::Regex::new(pattern)?;
+    let replacement = replacement.to_string();
+    Ok(Arc::new(move |message| regex.replace_all(message, replacement.as_str())))
+}
+
+/// Create a redactor masking card-number-like sequences of 13 to 19 digits, keeping the last four
+///
+/// Digits may be separated by single spaces or dashes, such as `4111 1111 1111 1111`.
+#[cfg(feature = "redact")]
+pub fn card_number_redactor() -> Redactor {
+    let regex = regex::Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").expect("card number pattern is valid");
+    Arc::new(move |message| {
+        regex.replace_all(message, |captures: &regex::Captures| {
+            let number = &captures[0];
+            let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+            let mut seen = 0;
+            number
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_digit() {
+                        seen += 1;
+                        if seen <= digits - 4 {
+                            return '*';
+                        }
+                    }
+                    c
+                })
+                .collect::<String>()
+        })
+    })
+}
+
+#[cfg(all(test, feature = "redact"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_numbers_keep_only_their_last_four_digits() {
+        let redact = card_number_redactor();
+        assert_eq!(redact("paid with 4111111111111111"), "paid with ************1111");
+        assert_eq!(redact("card 4111 1111 1111 1111 declined"), "card **** **** **** 1111 declined");
+        assert_eq!(redact("order 12345 shipped"), "order 12345 shipped");
+    }
+
+    #[test]
+    fn regex_redactors_replace_every_match() {
+        let redact = regex_redactor(r"token=\w+", "token=<redacted>").unwrap();
+        assert_eq!(redact("token=abc and token=def"), "token=<redacted> and token=<redacted>");
+        assert!(regex_redactor("(", "").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use log::{Level, Record};
+use sentry_core::protocol::{Breadcrumb, Event, Map, Value};
+
+/// Configuration of the records forwarded to Sentry
+///
+/// Records at `event_level` or above are captured as events, records at `breadcrumb_level` or
+/// above are added as breadcrumbs, which Sentry attaches to the next event. Nothing is sent
+/// unless the Sentry client has been initialized with `sentry::init`.
+#[derive(Clone, Debug)]
+pub struct SentryConfig {
+    /// Minimum level captured as an event (default is `Error`)
+    pub event_level: Level,
+    /// Minimum level added as a breadcrumb (default is `Warn`)
+    pub breadcrumb_level: Level,
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        SentryConfig {
+            event_level: Level::Error,
+            breadcrumb_level: Level::Warn,
+        }
+    }
+}
+
+impl SentryConfig {
+    /// Forward `record` with its already rendered message
+    pub(crate) fn report(&self, record: &Record, message: &str) {
+        let message = message.strip_suffix('\n').unwrap_or(message).to_string();
+        let mut location = Map::new();
+        if let Some(file) = record.file() {
+            location.insert("file".to_string(), Value::from(file));
+        }
+        if let Some(line) = record.line() {
+            location.insert("line".to_string(), Value::from(line));
+        }
+
+        if record.level() <= self.event_level {
+            sentry_core::capture_event(Event {
+                level: sentry_level(record.level()),
+                message: Some(message),
+                logger: Some(record.target().to_string()),
+                extra: location,
+                ..Event::default()
+            });
+        } else if record.level() <= self.breadcrumb_level {
+            sentry_core::add_breadcrumb(Breadcrumb {
+                ty: "log".to_string(),
+                category: Some(record.target().to_string()),
+                level: sentry_level(record.level()),
+                message: Some(message),
+                data: location,
+                ..Breadcrumb::default()
+            });
+        }
+    }
+}
+
+fn sentry_level(level: Level) -> sentry_core::Level {
+    match level {
+        Level::Error => sentry_core::Level::Error,
+        Level::Warn => sentry_core::Level::Warning,
+        Level::Info => sentry_core::Level::Info,
+        Level::Debug | Level::Trace => sentry_core::Level::Debug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(config: &SentryConfig, level: Level, message: &str) {
+        config.report(
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .level(level)
+                .target("my_app::db")
+                .file(Some("src/db/pool.rs"))
+                .line(Some(42))
+                .build(),
+            message,
+        );
+    }
+
+    #[test]
+    fn errors_are_captured_as_events_with_the_warnings_before_them() {
+        let config = SentryConfig::default();
+        let events = sentry_core::test::with_captured_events(|| {
+            report(&config, Level::Info, "connecting\n");
+            report(&config, Level::Warn, "slow query\n");
+            report(&config, Level::Error, "pool exhausted\n");
+        });
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.level, sentry_core::Level::Error);
+        assert_eq!(event.message.as_deref(), Some("pool exhausted"));
+        assert_eq!(event.logger.as_deref(), Some("my_app::db"));
+        assert_eq!(event.extra.get("file"), Some(&Value::from("src/db/pool.rs")));
+        assert_eq!(event.extra.get("line"), Some(&Value::from(42)));
+        let breadcrumbs: Vec<_> = event.breadcrumbs.iter().map(|breadcrumb| breadcrumb.message.as_deref()).collect();
+        assert_eq!(breadcrumbs, [Some("slow query")]);
+    }
+
+    #[test]
+    fn event_level_is_configurable() {
+        let config = SentryConfig {
+            event_level: Level::Warn,
+            ..SentryConfig::default()
+        };
+        let events = sentry_core::test::with_captured_events(|| report(&config, Level::Warn, "slow query\n"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, sentry_core::Level::Warning);
+    }
+}
@@ -0,0 +1,36 @@
+use log::LevelFilter;
+use std::sync::OnceLock;
+
+use crate::{ColorMode, LevelSource, LoggerPreset};
+
+static EFFECTIVE_CONFIG: OnceLock<ResolvedConfig> = OnceLock::new();
+
+/// The configuration in effect after init, with `RUST_LOG`, `RIMPLOG_COLOR` and fallbacks applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedConfig {
+    /// The default level from `level`, `Info` if it was invalid
+    pub level: LevelFilter,
+    /// The most verbose level any record can pass the filter with, including `RUST_LOG` directives
+    pub max_level: LevelFilter,
+    /// The value of `RUST_LOG` at init
+    pub rust_log: Option<String>,
+    pub level_source: LevelSource,
+    pub preset: LoggerPreset,
+    pub only_project_logs: bool,
+    /// Where records are written, such as `console` or `file 'app.log'`
+    pub sink: String,
+    pub color: ColorMode,
+}
+
+/// Get the configuration in effect for the global logger, `None` before init
+///
+/// This is a snapshot taken at init, later level changes such as [`set_max_level`](crate::set_max_level)
+/// are not reflected.
+pub fn effective_config() -> Option<&'static ResolvedConfig> {
+    EFFECTIVE_CONFIG.get()
+}
+
+/// Record the configuration of the global logger
+pub(crate) fn store(config: ResolvedConfig) {
+    let _ = EFFECTIVE_CONFIG.set(config);
+}
@@ -0,0 +1,50 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local! {
+    /// State of the xorshift generator of the `log_*_sampled` macros, seeded randomly per thread
+    static SAMPLE_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// Get whether a call of a `log_*_sampled` macro logs, with probability `rate` between 0 and 1
+#[doc(hidden)]
+pub fn __sample(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    let random = SAMPLE_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+    // The 53 high bits make a uniform float in [0, 1)
+    ((random >> 11) as f64 / (1u64 << 53) as f64) < rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_about_the_given_rate() {
+        const DRAWS: usize = 100_000;
+        for rate in [0.01, 0.25, 0.5, 0.9] {
+            let kept = (0..DRAWS).filter(|_| __sample(rate)).count();
+            let observed = kept as f64 / DRAWS as f64;
+            // More than six standard deviations away for these rates
+            assert!((observed - rate).abs() < 0.01, "rate {} kept {}", rate, observed);
+        }
+    }
+
+    #[test]
+    fn rates_outside_zero_and_one_keep_all_or_nothing() {
+        assert!((0..1000).all(|_| __sample(1.0)));
+        assert!((0..1000).all(|_| __sample(2.0)));
+        assert!((0..1000).all(|_| !__sample(0.0)));
+        assert!((0..1000).all(|_| !__sample(-1.0)));
+    }
+}
@@ -0,0 +1,149 @@
+use log::{LevelFilter, Record};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::console::StdoutSink;
+use crate::format::{strip_ansi, RecordFormatter};
+use crate::{ColorMode, LoggerBuilder, LoggerPreset};
+
+/// Also called with every record written, see `record_mirror`
+pub type MirrorCallback = Arc<dyn Fn(&Record) + Send + Sync>;
+
+/// Where an additional sink writes, see [`SinkConfig`]
+#[derive(Clone)]
+pub enum SinkTarget {
+    Stdout,
+    Stderr,
+    /// A file that records are appended to
+    File(PathBuf),
+    /// Any writer, such as a buffer shared with a test
+    Writer(Arc<Mutex<dyn Write + Send>>),
+}
+
+impl fmt::Debug for SinkTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkTarget::Stdout => f.write_str("Stdout"),
+            SinkTarget::Stderr => f.write_str("Stderr"),
+            SinkTarget::File(path) => f.debug_tuple("File").field(path).finish(),
+            SinkTarget::Writer(_) => f.write_str("Writer(..)"),
+        }
+    }
+}
+
+/// An additional sink with its own preset and level, next to the main one
+///
+/// Every sink formats records by itself, so a `FULL` file can be mirrored by a `SIMPLE`
+/// console. The other options are shared with the main sink. Colors are only written to
+/// `Stdout` and `Stderr`, as `color` allows.
+#[derive(Clone, Debug)]
+pub struct SinkConfig {
+    pub preset: LoggerPreset,
+    /// Most verbose level written, regardless of `level` and `RUST_LOG`
+    pub level: LevelFilter,
+    pub target: SinkTarget,
+}
+
+/// A [`SinkConfig`] that is ready to write
+pub(crate) struct Sink {
+    pub(crate) level: LevelFilter,
+    writer: Writer,
+    formatter: RecordFormatter,
+    colors: bool,
+}
+
+enum Writer {
+    /// The stdout shared with the main sink, buffered as set by `stdout_buffering`
+    Stdout(Arc<StdoutSink>),
+    Stderr,
+    Shared(Arc<Mutex<dyn Write + Send>>),
+}
+
+impl Sink {
+    /// Open the sink, formatting with `config` and the sink's preset
+    pub(crate) fn open(
+        sink: SinkConfig,
+        config: &LoggerBuilder,
+        color_mode: ColorMode,
+        project_name: &str,
+        stdout: &Arc<StdoutSink>,
+    ) -> io::Result<Self> {
+        let terminal_colors = |is_terminal: bool| match color_mode {
+            ColorMode::Auto => is_terminal,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        };
+        #[cfg(feature = "json")]
+        let is_console = matches!(sink.target, SinkTarget::Stdout | SinkTarget::Stderr);
+        let (writer, colors) = match sink.target {
+            SinkTarget::Stdout => (Writer::Stdout(Arc::clone(stdout)), terminal_colors(io::stdout().is_terminal())),
+            SinkTarget::Stderr => (Writer::Stderr, terminal_colors(io::stderr().is_terminal())),
+            SinkTarget::File(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                (Writer::Shared(Arc::new(Mutex::new(file))), false)
+            }
+            SinkTarget::Writer(writer) => (Writer::Shared(writer), false),
+        };
+        // A formatter of its own, so `collapse_consecutive` and friends track this sink separately
+        let mut config = config.clone();
+        config.preset = sink.preset;
+        config.progress_aware = false;
+        // JSON is only indented on the console
+        #[cfg(feature = "json")]
+        if !is_console {
+            config.prettify_json_messages = false;
+        }
+        Ok(Sink {
+            level: sink.level,
+            writer,
+            formatter: RecordFormatter::new(config, project_name.to_string()),
+            colors,
+        })
+    }
+
+    /// Write `Stderr` to the original stderr saved by `capture_stderr`, so its lines aren't captured again
+    pub(crate) fn use_original_stderr(&mut self, stderr: &File) {
+        if let Writer::Stderr = self.writer {
+            match stderr.try_clone() {
+                Ok(stderr) => self.writer = Writer::Shared(Arc::new(Mutex::new(stderr))),
+                Err(err) => eprintln!("Failed to reuse the original stderr: {}, its lines may be logged again", err),
+            }
+        }
+    }
+
+    pub(crate) fn write(&self, record: &Record) {
+        if record.level() > self.level {
+            return;
+        }
+        let mut bytes = Vec::new();
+        if self.formatter.write_record(&mut bytes, record).is_err() || bytes.is_empty() {
+            return;
+        }
+        let stripped;
+        let bytes = if self.colors || self.formatter.is_binary() {
+            &bytes
+        } else {
+            stripped = strip_ansi(&String::from_utf8_lossy(&bytes));
+            stripped.as_bytes()
+        };
+        let _ = match &self.writer {
+            Writer::Stdout(stdout) => stdout.write(bytes),
+            Writer::Stderr => io::stderr().lock().write_all(bytes),
+            Writer::Shared(writer) => writer.lock().unwrap_or_else(|e| e.into_inner()).write_all(bytes),
+        };
+    }
+
+    pub(crate) fn flush(&self) {
+        let _ = match &self.writer {
+            Writer::Stdout(stdout) => {
+                stdout.flush();
+                Ok(())
+            }
+            Writer::Stderr => io::stderr().flush(),
+            Writer::Shared(writer) => writer.lock().unwrap_or_else(|e| e.into_inner()).flush(),
+        };
+    }
+}
@@ -0,0 +1,106 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static SPAN_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Number of runs and total duration of the spans by name, `None` unless `span_timing_summary` is set
+static SPAN_TIMINGS: Mutex<Option<HashMap<String, (u64, Duration)>>> = Mutex::new(None);
+
+/// Get the span depth of the current thread
+pub(crate) fn span_depth() -> usize {
+    SPAN_DEPTH.with(|depth| depth.get())
+}
+
+/// Start accumulating the durations of the spans for [`log_timing_summary`]
+pub(crate) fn enable_timings() {
+    SPAN_TIMINGS.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(HashMap::new);
+}
+
+/// Log the number of runs, total and average duration of every span name at info level,
+/// the longest total first
+pub(crate) fn log_timing_summary() {
+    let Some(timings) = SPAN_TIMINGS.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    if timings.is_empty() {
+        return;
+    }
+    let mut timings = timings.into_iter().collect::<Vec<_>>();
+    timings.sort_by(|(_, (_, a)), (_, (_, b))| b.cmp(a));
+    let target = crate::project_name();
+    crate::log_record(log::Level::Info, target, file!(), line!(), format_args!("span timings:\n"));
+    for (name, (count, total)) in timings {
+        let average = average(total, count);
+        crate::log_record(
+            log::Level::Info,
+            target,
+            file!(),
+            line!(),
+            format_args!("  {}: {} runs, total {:?}, average {:?}\n", name, count, total, average),
+        );
+    }
+}
+
+/// Get the average of `count` durations adding up to `total`, in nanoseconds so counts past `u32::MAX` aren't cut
+fn average(total: Duration, count: u64) -> Duration {
+    let nanos = total.as_nanos() / u128::from(count.max(1));
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+/// Guard of a span created with [`span!`](crate::span)
+///
+/// Logs `-> name` when created and `<- name` when dropped, both at debug level.
+/// While the guard is alive, messages logged on the same thread are indented by two spaces.
+pub struct SpanGuard {
+    name: String,
+    target: &'static str,
+    file: &'static str,
+    line: u32,
+    start: Instant,
+}
+
+impl SpanGuard {
+    /// Enter a span, use the [`span!`](crate::span) macro instead of calling this directly
+    pub fn enter(name: impl Into<String>, target: &'static str, file: &'static str, line: u32) -> Self {
+        let guard = SpanGuard {
+            name: name.into(),
+            target,
+            file,
+            line,
+            start: Instant::now(),
+        };
+        crate::log_record(log::Level::Debug, target, file, line, format_args!("-> {}\n", guard.name));
+        SPAN_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        guard
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        SPAN_DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+        if let Some(timings) = SPAN_TIMINGS.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let (count, total) = timings.entry(self.name.clone()).or_default();
+            *count += 1;
+            *total += self.start.elapsed();
+        }
+        crate::log_record(log::Level::Debug, self.target, self.file, self.line, format_args!("<- {}\n", self.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_divides_by_counts_past_u32() {
+        assert_eq!(average(Duration::from_secs(6), 3), Duration::from_secs(2));
+        assert_eq!(average(Duration::from_secs(1), 0), Duration::from_secs(1));
+        // Truncated to 32 bits, this count would be 1
+        let count = u64::from(u32::MAX) + 2;
+        assert_eq!(average(Duration::from_nanos(count * 10), count), Duration::from_nanos(10));
+    }
+}
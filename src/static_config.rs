@@ -0,0 +1,112 @@
+use log::LevelFilter;
+
+use crate::{ColorMode, LevelSource, LoggerBuilder, LoggerPreset, PathDepth, TimePreset};
+
+/// The settings of a [`LoggerBuilder`] that can be declared in a `const` or `static`
+///
+/// `LoggerBuilder` owns strings and callbacks, so it can't be built at compile time. This
+/// covers the common settings with `const` setters instead, and checks them at compile time:
+/// the level is a [`LevelFilter`] and the time format a [`TimePreset`] rather than strings.
+/// Call [`builder`](StaticConfig::builder) to get a `LoggerBuilder` to initialize or customize.
+///
+/// # Example
+/// ```no_run
+/// use rimplog::StaticConfig;
+///
+/// static CONFIG: StaticConfig = StaticConfig::FULL_DEBUG.only_project_logs(true);
+///
+/// rimplog::init_logger(CONFIG.builder());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaticConfig {
+    preset: LoggerPreset,
+    level: LevelFilter,
+    only_project_logs: bool,
+    path_depth: PathDepth,
+    level_source: LevelSource,
+    color: Option<ColorMode>,
+    time_preset: Option<TimePreset>,
+}
+
+impl StaticConfig {
+    /// `FULL` at debug level, for development
+    pub const FULL_DEBUG: StaticConfig = StaticConfig::new(LoggerPreset::FULL).level(LevelFilter::Debug);
+    /// `THREAD` at info level with RFC 3339 timestamps and no colors, for services
+    pub const THREAD_INFO: StaticConfig = StaticConfig::new(LoggerPreset::THREAD)
+        .color(ColorMode::Never)
+        .time_preset(TimePreset::Rfc3339);
+    /// `SIMPLE` at info level, for command line tools
+    pub const SIMPLE_INFO: StaticConfig = StaticConfig::new(LoggerPreset::SIMPLE);
+
+    /// Create a configuration using `preset`, with the defaults of [`LoggerBuilder`] otherwise
+    pub const fn new(preset: LoggerPreset) -> Self {
+        StaticConfig {
+            preset,
+            level: LevelFilter::Info,
+            only_project_logs: false,
+            path_depth: PathDepth::Full,
+            level_source: LevelSource::EnvThenBuilder,
+            color: None,
+            time_preset: None,
+        }
+    }
+
+    /// Set the default level
+    pub const fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set whether to only write the records of the project
+    pub const fn only_project_logs(mut self, only_project_logs: bool) -> Self {
+        self.only_project_logs = only_project_logs;
+        self
+    }
+
+    /// Set how much of the record paths to show
+    pub const fn path_depth(mut self, path_depth: PathDepth) -> Self {
+        self.path_depth = path_depth;
+        self
+    }
+
+    /// Set how `RUST_LOG` combines with the level
+    pub const fn level_source(mut self, level_source: LevelSource) -> Self {
+        self.level_source = level_source;
+        self
+    }
+
+    /// Set when to write colors, instead of `RIMPLOG_COLOR` and auto detection
+    pub const fn color(mut self, color: ColorMode) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Set the time format from a [`TimePreset`]
+    pub const fn time_preset(mut self, time_preset: TimePreset) -> Self {
+        self.time_preset = Some(time_preset);
+        self
+    }
+
+    /// Get a [`LoggerBuilder`] with these settings
+    pub fn builder(&self) -> LoggerBuilder {
+        let builder = LoggerBuilder {
+            level: self.level.as_str().to_lowercase(),
+            preset: self.preset,
+            only_project_logs: self.only_project_logs,
+            path_depth: self.path_depth,
+            level_source: self.level_source,
+            color: self.color,
+            ..LoggerBuilder::default()
+        };
+        match self.time_preset {
+            Some(time_preset) => builder.time_preset(time_preset),
+            None => builder,
+        }
+    }
+}
+
+impl From<StaticConfig> for LoggerBuilder {
+    fn from(config: StaticConfig) -> Self {
+        config.builder()
+    }
+}
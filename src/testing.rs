@@ -0,0 +1,86 @@
+//! Helpers for testing code that logs with rimplog
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::format::{strip_ansi, RecordFormatter};
+use crate::{Clock, LoggerBuilder};
+
+/// A [`Clock`] that always returns the same time, for deterministic snapshots of log output
+///
+/// # Example
+/// ```no_run
+/// use rimplog::{testing::FixedClock, LoggerBuilder};
+///
+/// let clock = FixedClock::new(chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap());
+/// let builder = LoggerBuilder::default().clock(clock);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock {
+    time: DateTime<FixedOffset>,
+}
+
+impl FixedClock {
+    /// Create a clock stopped at `time`
+    pub fn new(time: DateTime<FixedOffset>) -> Self {
+        FixedClock { time }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.time
+    }
+}
+
+/// Format a single record with `builder` and return the line without colors, for golden tests of
+/// the output layout
+///
+/// Combine it with a [`FixedClock`] so the timestamp is deterministic. Nothing is logged, and the
+/// binary preset is not supported.
+///
+/// # Example
+/// ```
+/// use rimplog::{testing::{format_record, FixedClock}, LoggerBuilder, LoggerPreset};
+///
+/// let clock = FixedClock::new(chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap());
+/// let builder = LoggerBuilder { preset: LoggerPreset::SIMPLE, ..LoggerBuilder::default() }.clock(clock);
+/// let record = log::Record::builder()
+///     .args(format_args!("ready\n"))
+///     .level(log::Level::Info)
+///     .target("my_app")
+///     .build();
+/// assert_eq!(format_record(builder, &record), "[ 2024-10-18 09:30:00 INFO]  ready\n");
+/// ```
+pub fn format_record(builder: LoggerBuilder, record: &log::Record) -> String {
+    let formatter = RecordFormatter::new(builder, crate::project_name().to_string());
+    if !formatter.logs_target(record.target()) {
+        return String::new();
+    }
+    let mut buf = Vec::new();
+    let _ = formatter.write_record(&mut buf, record);
+    strip_ansi(&String::from_utf8_lossy(&buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoggerPreset;
+
+    #[test]
+    fn fixed_clock_pins_the_timestamp() {
+        let clock = FixedClock::new(DateTime::parse_from_rfc3339("2024-10-18T09:30:00.250+08:00").unwrap());
+        assert_eq!(clock.now(), clock.now());
+        let builder = LoggerBuilder {
+            preset: LoggerPreset::SIMPLE,
+            time_format: "%Y-%m-%dT%H:%M:%S%.3f%:z".to_string(),
+            ..LoggerBuilder::default()
+        }
+        .clock(clock);
+        let record = log::Record::builder()
+            .args(format_args!("ready\n"))
+            .level(log::Level::Info)
+            .target("rimplog")
+            .build();
+        assert_eq!(format_record(builder, &record), "[ 2024-10-18T09:30:00.250+08:00 INFO]  ready\n");
+    }
+}
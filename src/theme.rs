@@ -0,0 +1,79 @@
+use colored::{Color, ColoredString, Colorize};
+use log::Level;
+
+/// Built-in level color themes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorTheme {
+    /// Red, yellow, green, blue and magenta
+    #[default]
+    Default,
+    /// The accent colors of the Solarized palette
+    Solarized,
+    /// No colors, levels are only bold
+    Monochrome,
+    /// Bright colors for busy or low-contrast terminals
+    HighContrast,
+}
+
+impl ColorTheme {
+    /// Get the color of `level`, `None` means no color
+    pub fn level_color(self, level: Level) -> Option<Color> {
+        let color = match self {
+            ColorTheme::Default => match level {
+                Level::Error => Color::Red,
+                Level::Warn => Color::Yellow,
+                Level::Info => Color::Green,
+                Level::Debug => Color::Blue,
+                Level::Trace => Color::Magenta,
+            },
+            ColorTheme::Solarized => match level {
+                Level::Error => Color::TrueColor { r: 220, g: 50, b: 47 },
+                Level::Warn => Color::TrueColor { r: 181, g: 137, b: 0 },
+                Level::Info => Color::TrueColor { r: 133, g: 153, b: 0 },
+                Level::Debug => Color::TrueColor { r: 38, g: 139, b: 210 },
+                Level::Trace => Color::TrueColor { r: 211, g: 54, b: 130 },
+            },
+            ColorTheme::Monochrome => return None,
+            ColorTheme::HighContrast => match level {
+                Level::Error => Color::BrightRed,
+                Level::Warn => Color::BrightYellow,
+                Level::Info => Color::BrightGreen,
+                Level::Debug => Color::BrightCyan,
+                Level::Trace => Color::BrightWhite,
+            },
+        };
+        Some(color)
+    }
+
+    /// Style a level label with the theme
+    pub(crate) fn paint(self, level: Level, label: &str) -> ColoredString {
+        match self.level_color(level) {
+            Some(color) => label.color(color).bold(),
+            None => label.bold(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn painted(theme: ColorTheme, level: Level) -> String {
+        let _colors = crate::console::force_colors();
+        theme.paint(level, level.as_str()).to_string()
+    }
+
+    #[test]
+    fn high_contrast_warnings_differ_from_default_ones() {
+        let default = painted(ColorTheme::Default, Level::Warn);
+        let high_contrast = painted(ColorTheme::HighContrast, Level::Warn);
+        assert_eq!(default, "\x1b[1;33mWARN\x1b[0m");
+        assert_eq!(high_contrast, "\x1b[1;93mWARN\x1b[0m");
+    }
+
+    #[test]
+    fn monochrome_keeps_labels_bold_without_color() {
+        assert_eq!(painted(ColorTheme::Monochrome, Level::Error), "\x1b[1mERROR\x1b[0m");
+        assert_eq!(ColorTheme::Monochrome.level_color(Level::Error), None);
+    }
+}
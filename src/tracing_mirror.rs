@@ -0,0 +1,22 @@
+use log::{Level, Record};
+
+/// Emit `record` as a `tracing` event with its rendered message, for `tracing_mirror`
+///
+/// `tracing` needs the target and location of an event at compile time, so they are the
+/// `log.target`, `log.file` and `log.line` fields, as `tracing-log` names them.
+pub(crate) fn emit(record: &Record, message: &str) {
+    let message = message.trim_end_matches('\n');
+    let (target, file, line) = (record.target(), record.file(), record.line());
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!($level, log.target = target, log.file = file, log.line = line, "{}", message)
+        };
+    }
+    match record.level() {
+        Level::Error => event!(tracing::Level::ERROR),
+        Level::Warn => event!(tracing::Level::WARN),
+        Level::Info => event!(tracing::Level::INFO),
+        Level::Debug => event!(tracing::Level::DEBUG),
+        Level::Trace => event!(tracing::Level::TRACE),
+    }
+}
@@ -0,0 +1,228 @@
+use chrono::format::{Item, StrftimeItems};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use crate::LoggerBuilder;
+
+/// Placeholders supported by `file_header` and `file_footer`
+const FILE_TEMPLATE_PLACEHOLDERS: [&str; 4] = ["time", "pid", "name", "version"];
+
+/// A problem found by [`LoggerBuilder::validate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `level` is not a level name
+    InvalidLevel(String),
+    /// `time_format` contains an invalid specifier
+    InvalidTimeFormat(String),
+    /// A log file can't be opened for writing
+    UnwritableFile {
+        path: PathBuf,
+        reason: String,
+    },
+    /// `file_header` or `file_footer` contains an empty or unknown placeholder
+    InvalidPlaceholder {
+        field: &'static str,
+        placeholder: String,
+    },
+    /// `timezone` is not an IANA time zone name
+    #[cfg(feature = "tz")]
+    InvalidTimezone(String),
+    /// `target_regex` doesn't compile
+    #[cfg(feature = "regex")]
+    InvalidTargetRegex {
+        pattern: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::InvalidLevel(level) => write!(f, "invalid log level '{}'", level),
+            ConfigError::InvalidTimeFormat(format) => write!(f, "invalid time format '{}'", format),
+            ConfigError::UnwritableFile { path, reason } => {
+                write!(f, "log file '{}' is not writable: {}", path.display(), reason)
+            }
+            ConfigError::InvalidPlaceholder { field, placeholder } => {
+                write!(f, "unknown placeholder '{{{}}}' in {}", placeholder, field)
+            }
+            #[cfg(feature = "tz")]
+            ConfigError::InvalidTimezone(name) => write!(f, "invalid time zone '{}'", name),
+            #[cfg(feature = "regex")]
+            ConfigError::InvalidTargetRegex { pattern, reason } => {
+                write!(f, "invalid target regex '{}': {}", pattern, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl LoggerBuilder {
+    /// Check the configuration before init and return every problem found
+    ///
+    /// `init_logger` falls back to defaults and prints a warning for each of these problems,
+    /// call this first to fail early instead. Log files are not created.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if self.level.to_lowercase().parse::<log::LevelFilter>().is_err() {
+            errors.push(ConfigError::InvalidLevel(self.level.clone()));
+        }
+        if StrftimeItems::new(&self.time_format).any(|item| item == Item::Error) {
+            errors.push(ConfigError::InvalidTimeFormat(self.time_format.clone()));
+        }
+
+        let files = self.file.iter().chain(self.target_files.iter().map(|(_, path)| path));
+        for path in files {
+            if let Err(reason) = check_writable(path) {
+                errors.push(ConfigError::UnwritableFile {
+                    path: path.clone(),
+                    reason,
+                });
+            }
+        }
+
+        for (field, template) in [("file_header", &self.file_header), ("file_footer", &self.file_footer)] {
+            if let Some(template) = template {
+                errors.extend(unknown_placeholders(template).map(|placeholder| ConfigError::InvalidPlaceholder {
+                    field,
+                    placeholder,
+                }));
+            }
+        }
+
+        #[cfg(feature = "tz")]
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<chrono_tz::Tz>().is_err() {
+                errors.push(ConfigError::InvalidTimezone(timezone.clone()));
+            }
+        }
+        #[cfg(feature = "regex")]
+        if let Some(pattern) = &self.target_regex {
+            if let Err(err) = regex::Regex::new(pattern) {
+                errors.push(ConfigError::InvalidTargetRegex {
+                    pattern: pattern.clone(),
+                    reason: err.to_string(),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Check that a log file can be appended to, or created in an existing directory
+fn check_writable(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return OpenOptions::new().append(true).open(path).map(|_| ()).map_err(|err| err.to_string());
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match parent.metadata() {
+        Ok(metadata) if metadata.is_dir() && !metadata.permissions().readonly() => Ok(()),
+        Ok(_) => Err(format!("'{}' is not a writable directory", parent.display())),
+        Err(err) => Err(format!("'{}': {}", parent.display(), err)),
+    }
+}
+
+/// Get the placeholders of a template that are empty or not supported
+fn unknown_placeholders(template: &str) -> impl Iterator<Item = String> + '_ {
+    template.split('{').skip(1).filter_map(|rest| {
+        let placeholder = rest.split_once('}')?.0;
+        (!FILE_TEMPLATE_PLACEHOLDERS.contains(&placeholder)).then(|| placeholder.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validate a builder that only differs from the default by `f`
+    fn errors(f: impl FnOnce(&mut LoggerBuilder)) -> Vec<ConfigError> {
+        let mut builder = LoggerBuilder::default();
+        f(&mut builder);
+        builder.validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn default_builder_is_valid() {
+        assert_eq!(LoggerBuilder::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_level() {
+        assert_eq!(errors(|b| b.level = "verbose".to_string()), [ConfigError::InvalidLevel("verbose".to_string())]);
+        assert_eq!(errors(|b| b.level = "WARN".to_string()), []);
+    }
+
+    #[test]
+    fn invalid_time_format() {
+        assert_eq!(errors(|b| b.time_format = "%Y-%Q".to_string()), [ConfigError::InvalidTimeFormat("%Y-%Q".to_string())]);
+    }
+
+    #[test]
+    fn unwritable_files() {
+        let missing = std::env::temp_dir().join("rimplog-missing-dir").join("app.log");
+        let errors = errors(|b| {
+            b.file = Some(missing.clone());
+            b.target_files = vec![("db".to_string(), missing.clone())];
+        });
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.iter().all(|err| matches!(err, ConfigError::UnwritableFile { path, .. } if *path == missing)));
+        // Validating doesn't create the file
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn invalid_placeholders() {
+        let errors = errors(|b| {
+            b.file_header = Some("started {time} by {user}".to_string());
+            b.file_footer = Some("done {}".to_string());
+        });
+        assert_eq!(
+            errors,
+            [
+                ConfigError::InvalidPlaceholder {
+                    field: "file_header",
+                    placeholder: "user".to_string(),
+                },
+                ConfigError::InvalidPlaceholder {
+                    field: "file_footer",
+                    placeholder: String::new(),
+                },
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "unknown placeholder '{user}' in file_header");
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn invalid_timezone() {
+        assert_eq!(errors(|b| b.timezone = Some("Mars/Olympus".to_string())), [ConfigError::InvalidTimezone("Mars/Olympus".to_string())]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn invalid_target_regex() {
+        let errors = errors(|b| b.target_regex = Some("^app::(db".to_string()));
+        assert!(matches!(&errors[..], [ConfigError::InvalidTargetRegex { pattern, .. }] if pattern == "^app::(db"), "{:?}", errors);
+    }
+
+    #[test]
+    fn every_problem_is_returned_at_once() {
+        let errors = errors(|b| {
+            b.level = "loud".to_string();
+            b.time_format = "%Q".to_string();
+            b.file_footer = Some("{host}".to_string());
+        });
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+    }
+}
@@ -0,0 +1,100 @@
+//! Helpers shared by the integration tests
+//!
+//! Every test file is its own process with its own global logger, the tests of a file take
+//! turns installing their configuration on it with [`capture`].
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rimplog::testing::FixedClock;
+use rimplog::{ColorMode, LevelSource, LoggerBuilder, LoggerPreset};
+
+/// Environment variable set in the child processes of [`run_child`]
+pub const CHILD_VAR: &str = "RIMPLOG_TEST_CHILD";
+
+/// Held while a test owns the global logger
+static LOGGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Timestamp of [`clock`], as written by the default `time_format`
+pub const TIMESTAMP: &str = "2024-10-18 09:30:00";
+
+/// A clock stopped at [`TIMESTAMP`]
+pub fn clock() -> FixedClock {
+    FixedClock::new(chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap())
+}
+
+/// A builder with predictable output: `SIMPLE` at trace level, a fixed clock, no colors and `RUST_LOG` ignored
+pub fn builder() -> LoggerBuilder {
+    LoggerBuilder {
+        level: "trace".to_string(),
+        preset: LoggerPreset::SIMPLE,
+        color: Some(ColorMode::Never),
+        level_source: LevelSource::BuilderOnly,
+        ..LoggerBuilder::default()
+    }
+    .clock(clock())
+}
+
+/// Get a path in the temporary directory that no other test uses
+pub fn temp_path(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("rimplog-{}-{}-{}", std::process::id(), id, name))
+}
+
+/// Install `builder` as the global logger, writing to a temporary file, run `log` and return what was written
+///
+/// The global logger is installed by the first call and reconfigured by the next ones.
+pub fn capture(mut builder: LoggerBuilder, log: impl FnOnce()) -> String {
+    let _lock = LOGGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = temp_path("capture.log");
+    builder.file = Some(path.clone());
+    if !rimplog::reconfigure(builder.clone()) {
+        rimplog::try_init_logger(builder).expect("only the tests install the logger");
+    }
+    log();
+    rimplog::flush();
+    let output = fs::read_to_string(&path).unwrap_or_default();
+    let _ = fs::remove_file(&path);
+    output
+}
+
+/// Whether the test runs in a child process started by [`run_child`]
+pub fn is_child() -> bool {
+    env::var_os(CHILD_VAR).is_some()
+}
+
+/// Run only the test `name` of the current test binary in a child process, for tests that need
+/// the process streams or a fresh global logger
+pub fn run_child(name: &str) -> Output {
+    run_child_with_env(name, &[])
+}
+
+/// Like [`run_child`], with extra environment variables set in the child process
+pub fn run_child_with_env(name: &str, vars: &[(&str, &str)]) -> Output {
+    Command::new(env::current_exe().unwrap())
+        .args([name, "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_VAR, "1")
+        .env_remove("RUST_LOG")
+        .env_remove("RIMPLOG_COLOR")
+        .envs(vars.iter().copied())
+        .output()
+        .expect("the test binary can be run again")
+}
+
+/// Run the test `name` with [`run_child`] and check that it passed in the child process
+pub fn assert_child_passes(name: &str) {
+    let output = run_child(name);
+    assert!(
+        output.status.success(),
+        "{} failed in the child process\nstdout:\n{}\nstderr:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,457 @@
+mod common;
+
+use std::thread;
+
+use log::{Level, Record};
+use rimplog::testing::format_record;
+use rimplog::{ColorMode, Environment, ExternalDetail, LevelSource, LoggerBuilder, LoggerPreset, TimePreset};
+
+use common::builder;
+
+/// Format a record of `target` at `level` logged from `src/db/pool.rs:42`
+fn format(builder: LoggerBuilder, level: Level, target: &str, message: &str) -> String {
+    format_record(
+        builder,
+        &Record::builder()
+            .args(format_args!("{}\n", message))
+            .level(level)
+            .target(target)
+            .module_path(Some(target))
+            .file(Some("src/db/pool.rs"))
+            .line(Some(42))
+            .build(),
+    )
+}
+
+/// Run `f` on a thread named `worker`, so the thread segment is the same in every test
+fn on_worker<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| thread::Builder::new().name("worker".to_string()).spawn_scoped(scope, f).unwrap().join().unwrap())
+}
+
+#[test]
+fn time_presets_render_their_documented_shape() {
+    let cases = [
+        (TimePreset::Rfc3339, "2024-10-18T09:30:00+08:00"),
+        (TimePreset::Kitchen, "9:30 AM"),
+        (TimePreset::DateOnly, "2024-10-18"),
+        (TimePreset::TimeOnly, "09:30:00"),
+        (TimePreset::IsoWeek, "2024-W42-5"),
+        (TimePreset::DayOfYear, "2024-292"),
+    ];
+    for (preset, timestamp) in cases {
+        let line = format(builder().time_preset(preset), Level::Info, "rimplog", "ready");
+        assert_eq!(line, format!("[ {} INFO]  ready\n", timestamp), "{:?}", preset);
+    }
+}
+
+#[cfg(feature = "tz")]
+#[test]
+fn timezone_converts_the_timestamp() {
+    let zoned = |timezone: &str, time: &str| {
+        let clock = rimplog::testing::FixedClock::new(chrono::DateTime::parse_from_rfc3339(time).unwrap());
+        let config = LoggerBuilder {
+            timezone: Some(timezone.to_string()),
+            time_format: "%Y-%m-%d %H:%M %:z".to_string(),
+            preset: LoggerPreset::SIMPLE,
+            ..LoggerBuilder::default()
+        }
+        .clock(clock);
+        format(config, Level::Info, "rimplog", "ready")
+    };
+    assert_eq!(zoned("America/New_York", "2024-10-18T09:30:00+08:00"), "[ 2024-10-17 21:30 -04:00 INFO]  ready\n");
+    assert_eq!(zoned("America/New_York", "2024-01-15T12:00:00Z"), "[ 2024-01-15 07:00 -05:00 INFO]  ready\n");
+    // Invalid names keep the time of the clock
+    assert_eq!(zoned("Mars/Olympus_Mons", "2024-10-18T09:30:00+08:00"), "[ 2024-10-18 09:30 +08:00 INFO]  ready\n");
+}
+
+#[test]
+fn sanitize_messages_escapes_control_characters() {
+    let config = LoggerBuilder {
+        sanitize_messages: true,
+        ..builder()
+    };
+    let line = format(config, Level::Warn, "rimplog", "user \x1b[31madmin\r\n[ fake line");
+    assert_eq!(line, "[ 2024-10-18 09:30:00 WARN]  user \\x1b[31madmin\\r\\n[ fake line\n");
+
+    // Otherwise the escape is written as a color, which `format_record` strips
+    let line = format(builder(), Level::Warn, "rimplog", "user \x1b[31madmin");
+    assert_eq!(line, "[ 2024-10-18 09:30:00 WARN]  user admin\n");
+}
+
+#[test]
+fn external_detail_controls_external_records() {
+    let external = |external_detail| {
+        let config = LoggerBuilder {
+            external_detail,
+            ..builder().preset(LoggerPreset::FULL)
+        };
+        on_worker(|| format(config, Level::Info, "hyper::proto", "ready"))
+    };
+    assert_eq!(
+        external(ExternalDetail::TargetAndPath),
+        "2024-10-18 09:30:00 INFO  [worker] [[hyper::proto] src/db/pool.rs:42] ready\n"
+    );
+    assert_eq!(external(ExternalDetail::TargetOnly), "2024-10-18 09:30:00 INFO  [worker] [hyper::proto] ready\n");
+    assert_eq!(external(ExternalDetail::Hidden), "");
+
+    // Project records are not affected
+    let config = LoggerBuilder {
+        external_detail: ExternalDetail::Hidden,
+        ..builder().preset(LoggerPreset::FULL)
+    };
+    assert_eq!(
+        on_worker(|| format(config, Level::Info, "rimplog::db", "ready")),
+        "2024-10-18 09:30:00 INFO  [worker] [src/db/pool.rs:42] ready\n"
+    );
+}
+
+#[test]
+fn multiline_indents_continuation_lines_under_the_message() {
+    let config = LoggerBuilder {
+        multiline: true,
+        ..builder()
+    };
+    let line = format(config, Level::Info, "rimplog", "Config {\n    port: 8080,\n}");
+    assert_eq!(
+        line,
+        concat!(
+            "[ 2024-10-18 09:30:00 INFO]  Config {\n",
+            "                           |     port: 8080,\n",
+            "                           | }\n",
+        )
+    );
+}
+
+#[test]
+fn numeric_level_shows_syslog_severities() {
+    let config = LoggerBuilder {
+        numeric_level: true,
+        pad_level: false,
+        ..builder()
+    };
+    let cases = [(Level::Error, "3"), (Level::Warn, "4"), (Level::Info, "6"), (Level::Debug, "7"), (Level::Trace, "7")];
+    for (level, severity) in cases {
+        let line = format(config.clone(), level, "rimplog", "ready");
+        assert_eq!(line, format!("[ 2024-10-18 09:30:00 {}] ready\n", severity), "{:?}", level);
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn show_task_id_tells_tasks_apart() {
+    let config = LoggerBuilder {
+        show_task_id: true,
+        ..builder().preset(LoggerPreset::THREAD)
+    };
+    let lines = on_worker(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let first = tokio::spawn({
+                let config = config.clone();
+                async move { format(config, Level::Info, "rimplog", "ready") }
+            });
+            let second = tokio::spawn({
+                let config = config.clone();
+                async move { format(config, Level::Info, "rimplog", "ready") }
+            });
+            [first.await.unwrap(), second.await.unwrap()]
+        })
+    });
+    let task_ids: Vec<&str> = lines
+        .iter()
+        .map(|line| {
+            let thread = line.split_once("[worker#").and_then(|(_, rest)| rest.split_once(']')).map(|(id, _)| id);
+            thread.unwrap_or_else(|| panic!("no task id in {:?}", line))
+        })
+        .collect();
+    assert_ne!(task_ids[0], task_ids[1]);
+
+    // Outside a task the thread name is left alone
+    assert_eq!(on_worker(|| format(config, Level::Info, "rimplog", "ready")), "2024-10-18 09:30:00 INFO  [worker] ready\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn target_regex_only_passes_matching_targets() {
+    let filtered = |pattern: &str, target: &str| {
+        let config = LoggerBuilder {
+            target_regex: Some(pattern.to_string()),
+            ..builder()
+        };
+        format(config, Level::Info, target, "ready")
+    };
+    let line = "[ 2024-10-18 09:30:00 INFO]  ready\n";
+    assert_eq!(filtered("^app::(db|cache)", "app::db::pool"), line);
+    assert_eq!(filtered("^app::(db|cache)", "app::cache"), line);
+    assert_eq!(filtered("^app::(db|cache)", "app::http"), "");
+    assert_eq!(filtered("^app::(db|cache)", "hyper::app::db"), "");
+    // An invalid regex is ignored
+    assert_eq!(filtered("^app::(db", "app::http"), line);
+}
+
+#[test]
+fn golden_preset_layouts() {
+    let cases = [
+        (LoggerPreset::FULL, Level::Info, "rimplog::db", "2024-10-18 09:30:00 INFO  [worker] [src/db/pool.rs:42] pool ready\n"),
+        (
+            LoggerPreset::FULL,
+            Level::Error,
+            "hyper::proto",
+            "2024-10-18 09:30:00 ERROR [worker] [[hyper::proto] src/db/pool.rs:42] pool ready\n",
+        ),
+        (LoggerPreset::THREAD, Level::Info, "rimplog::db", "2024-10-18 09:30:00 INFO  [worker] pool ready\n"),
+        (LoggerPreset::THREAD, Level::Error, "hyper::proto", "2024-10-18 09:30:00 ERROR [worker] pool ready\n"),
+        (LoggerPreset::SIMPLE, Level::Info, "rimplog::db", "[ 2024-10-18 09:30:00 INFO]  pool ready\n"),
+        (LoggerPreset::SIMPLE, Level::Error, "hyper::proto", "[ 2024-10-18 09:30:00 ERROR] pool ready\n"),
+    ];
+    for (preset, level, target, expected) in cases {
+        let line = on_worker(|| format(builder().preset(preset), level, target, "pool ready"));
+        assert_eq!(line, expected, "{:?} {}", preset, target);
+    }
+}
+
+#[test]
+fn trim_trailing_removes_the_space_before_the_newline() {
+    assert_eq!(format(builder(), Level::Info, "rimplog", ""), "[ 2024-10-18 09:30:00 INFO]\n");
+    assert_eq!(format(builder(), Level::Info, "rimplog", "two\t\nlines  "), "[ 2024-10-18 09:30:00 INFO]  two\nlines\n");
+    let config = LoggerBuilder {
+        trim_trailing: false,
+        ..builder()
+    };
+    assert_eq!(format(config, Level::Info, "rimplog", ""), "[ 2024-10-18 09:30:00 INFO]  \n");
+}
+
+#[test]
+fn show_module_path_adds_the_module_path_next_to_the_target() {
+    let config = LoggerBuilder {
+        show_module_path: true,
+        ..builder().preset(LoggerPreset::FULL)
+    };
+    let line = |module_path: Option<&'static str>| {
+        on_worker(|| {
+            let record = Record::builder()
+                .args(format_args!("ready\n"))
+                .level(Level::Info)
+                .target("hyper::proto")
+                .module_path(module_path)
+                .file(Some("src/db/pool.rs"))
+                .line(Some(42))
+                .build();
+            format_record(config.clone(), &record)
+        })
+    };
+    assert_eq!(
+        line(Some("hyper::proto::h1::conn")),
+        "2024-10-18 09:30:00 INFO  [worker] [[hyper::proto] src/db/pool.rs:42] [hyper::proto::h1::conn] ready\n"
+    );
+    assert_eq!(line(None), "2024-10-18 09:30:00 INFO  [worker] [[hyper::proto] src/db/pool.rs:42] [unknown] ready\n");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn prettify_json_messages_indents_json() {
+    let config = LoggerBuilder {
+        prettify_json_messages: true,
+        ..builder()
+    };
+    let line = format(config.clone(), Level::Info, "rimplog", r#"{"roles":["admin"],"user":42}"#);
+    assert_eq!(
+        line,
+        concat!(
+            "[ 2024-10-18 09:30:00 INFO]  {\n",
+            "  \"roles\": [\n",
+            "    \"admin\"\n",
+            "  ],\n",
+            "  \"user\": 42\n",
+            "}\n",
+        )
+    );
+    // Other messages are left alone
+    assert_eq!(format(config, Level::Info, "rimplog", "{not json}"), "[ 2024-10-18 09:30:00 INFO]  {not json}\n");
+}
+
+#[test]
+fn production_preset_disables_colors() {
+    let config = LoggerBuilder::preset_for(Environment::Production);
+    assert_eq!(config.color, Some(ColorMode::Never));
+    assert_eq!(config.level, "info");
+    #[cfg(not(feature = "json"))]
+    {
+        // Without the JSON preset, production records are one plain line each, with control characters escaped
+        assert_eq!(config.preset, LoggerPreset::THREAD);
+        assert!(config.sanitize_messages);
+        let line = on_worker(|| format(config.clock(common::clock()), Level::Warn, "rimplog", "disk\nfull"));
+        assert_eq!(line, "2024-10-18T09:30:00+08:00 WARN  [worker] disk\\nfull\n");
+    }
+    #[cfg(feature = "json")]
+    {
+        assert_eq!(config.preset, LoggerPreset::JSON);
+        let line = on_worker(|| format(config.clock(common::clock()), Level::Warn, "rimplog", "disk full"));
+        assert!(!line.contains('\x1b'), "{:?}", line);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            serde_json::json!({
+                "timestamp": "2024-10-18T09:30:00+08:00",
+                "level": "WARN",
+                "target": "rimplog",
+                "thread": "worker",
+                "file": "src/db/pool.rs",
+                "line": 42,
+                "message": "disk full",
+            })
+        );
+    }
+
+    let config = LoggerBuilder::preset_for(Environment::Test);
+    assert_eq!(config.color, Some(ColorMode::Never));
+    assert_eq!(config.level_source, LevelSource::BuilderOnly);
+    assert_eq!(LoggerBuilder::preset_for(Environment::Development).preset, LoggerPreset::FULL);
+}
+
+#[test]
+fn thread_name_map_reuses_the_index_of_a_thread() {
+    let config = LoggerBuilder {
+        thread_name_map: true,
+        ..builder().preset(LoggerPreset::THREAD)
+    };
+    let on_thread = |name: &str| {
+        let config = config.clone();
+        thread::scope(|scope| {
+            let f = || [(); 2].map(|_| format(config.clone(), Level::Info, "rimplog", "ready"));
+            thread::Builder::new().name(name.to_string()).spawn_scoped(scope, f).unwrap().join().unwrap()
+        })
+    };
+    let index = |line: &str| line.split_once("[#").unwrap().1.split_once(']').unwrap().0.parse::<usize>().unwrap();
+
+    let [first, again] = on_thread("thread-name-map-a");
+    let a = index(&first);
+    assert_eq!(
+        first,
+        format!("(thread #{0} is thread-name-map-a)\n2024-10-18 09:30:00 INFO  [#{0}] ready\n", a)
+    );
+    assert_eq!(again, format!("2024-10-18 09:30:00 INFO  [#{}] ready\n", a));
+
+    let [other, _] = on_thread("thread-name-map-b");
+    assert_ne!(index(&other), a);
+    // A new thread with the same name gets the same index, without a notice
+    assert_eq!(on_thread("thread-name-map-a")[0], again);
+}
+
+#[test]
+fn target_width_aligns_the_message_column() {
+    let config = || LoggerBuilder {
+        target_width: Some(12),
+        external_detail: ExternalDetail::TargetOnly,
+        ..builder().preset(LoggerPreset::FULL)
+    };
+    let short = on_worker(|| format(config(), Level::Info, "hyper", "ready"));
+    let long = on_worker(|| format(config(), Level::Info, "hyper::proto::h1::conn", "ready"));
+    assert_eq!(short, "2024-10-18 09:30:00 INFO  [worker] [hyper       ] ready\n");
+    assert_eq!(long, "2024-10-18 09:30:00 INFO  [worker] [hyper::prot…] ready\n");
+    let column = |line: &str| line.split_once("ready").map(|(head, _)| head.chars().count());
+    assert_eq!(column(&short), column(&long));
+}
+
+#[test]
+fn build_id_is_shown_before_the_message() {
+    let config = LoggerBuilder {
+        build_id: Some("3f9c2ab".to_string()),
+        ..builder()
+    };
+    assert_eq!(format(config.clone(), Level::Info, "rimplog", "ready"), "[ 2024-10-18 09:30:00 INFO]  [3f9c2ab] ready\n");
+    let config = LoggerBuilder {
+        preset: LoggerPreset::THREAD,
+        ..config
+    };
+    assert_eq!(
+        on_worker(|| format(config, Level::Warn, "rimplog", "slow")),
+        "2024-10-18 09:30:00 WARN  [worker] [3f9c2ab] slow\n"
+    );
+}
+
+#[test]
+fn raw_is_exactly_the_message_and_a_newline() {
+    for message in ["ready", "", "  indented and padded  ", "two\nlines", "tab\t"] {
+        let line = format(builder().preset(LoggerPreset::RAW), Level::Info, "rimplog", message);
+        assert_eq!(line, format!("{}\n", message));
+    }
+}
+
+#[test]
+fn level_labels_are_padded_to_the_width_of_their_mode() {
+    let short = LoggerBuilder {
+        short_level: true,
+        ..builder()
+    };
+    let numeric = LoggerBuilder {
+        numeric_level: true,
+        ..builder()
+    };
+    let cases = [
+        (builder(), LoggerPreset::FULL, Level::Warn, "2024-10-18 09:30:00 WARN  [worker] [src/db/pool.rs:42] ready\n"),
+        (builder(), LoggerPreset::THREAD, Level::Warn, "2024-10-18 09:30:00 WARN  [worker] ready\n"),
+        (builder(), LoggerPreset::SIMPLE, Level::Warn, "[ 2024-10-18 09:30:00 WARN]  ready\n"),
+        (builder(), LoggerPreset::SIMPLE, Level::Error, "[ 2024-10-18 09:30:00 ERROR] ready\n"),
+        (short.clone(), LoggerPreset::FULL, Level::Info, "2024-10-18 09:30:00 I [worker] [src/db/pool.rs:42] ready\n"),
+        (short.clone(), LoggerPreset::THREAD, Level::Info, "2024-10-18 09:30:00 I [worker] ready\n"),
+        (short.clone(), LoggerPreset::SIMPLE, Level::Info, "[ 2024-10-18 09:30:00 I] ready\n"),
+        (short, LoggerPreset::SIMPLE, Level::Error, "[ 2024-10-18 09:30:00 E] ready\n"),
+        (numeric.clone(), LoggerPreset::FULL, Level::Info, "2024-10-18 09:30:00 6 [worker] [src/db/pool.rs:42] ready\n"),
+        (numeric.clone(), LoggerPreset::THREAD, Level::Info, "2024-10-18 09:30:00 6 [worker] ready\n"),
+        (numeric.clone(), LoggerPreset::SIMPLE, Level::Info, "[ 2024-10-18 09:30:00 6] ready\n"),
+        (numeric, LoggerPreset::SIMPLE, Level::Error, "[ 2024-10-18 09:30:00 3] ready\n"),
+    ];
+    for (config, preset, level, expected) in cases {
+        let line = on_worker(|| format(config.clone().preset(preset), level, "rimplog::db", "ready"));
+        assert_eq!(line, expected, "{:?} {:?}", preset, level);
+        // No stray space inside the brackets, other than the one opening `SIMPLE`
+        assert!(!line.contains(" ]"), "{:?}", line);
+        assert_eq!(line.matches("[ ").count(), usize::from(matches!(preset, LoggerPreset::SIMPLE)), "{:?}", line);
+    }
+}
+
+#[cfg(feature = "redact")]
+#[test]
+fn max_args_len_cuts_the_message_after_the_redactor() {
+    let config = LoggerBuilder {
+        max_args_len: Some(20),
+        redactor: Some(rimplog::card_number_redactor()),
+        ..builder()
+    };
+    // Cut first, the 12 digits left would be too short to be redacted
+    assert_eq!(
+        format(config, Level::Info, "rimplog", "charged 4111111111111111 for order 7"),
+        "[ 2024-10-18 09:30:00 INFO]  charged ************… (36 chars)\n"
+    );
+}
+
+#[test]
+fn max_args_len_cuts_a_large_pretty_debug_value() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Config {
+        name: &'static str,
+        items: Vec<u32>,
+    }
+    let config = Config {
+        name: "app",
+        items: (0..10_000).collect(),
+    };
+    let pretty = format!("{:#?}", config);
+    let line = format(
+        LoggerBuilder {
+            max_args_len: Some(60),
+            ..builder()
+        },
+        Level::Debug,
+        "rimplog",
+        &pretty,
+    );
+    assert_eq!(
+        line,
+        format!(
+            "[ 2024-10-18 09:30:00 DEBUG] Config {{\n    name: \"app\",\n    items: [\n        0,\n        1,…] }} ({} chars)\n",
+            pretty.chars().count()
+        )
+    );
+}
@@ -0,0 +1,747 @@
+//! Tests of what happens at init, each in a child process with a fresh global logger
+
+mod common;
+
+use std::env;
+use std::fs;
+#[cfg(feature = "json")]
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rimplog::{log_debug, log_error, log_info, log_warn, LevelSource, LoggerBuilder};
+#[cfg(unix)]
+use rimplog::StdoutBuffering;
+#[cfg(feature = "json")]
+use rimplog::NetworkSink;
+#[cfg(any(unix, feature = "json"))]
+use rimplog::{LoggerPreset, SinkConfig, SinkTarget};
+
+use common::{assert_child_passes, builder, is_child, run_child, run_child_with_env, TIMESTAMP};
+
+#[test]
+fn unopenable_file_falls_back_to_stdout() {
+    if is_child() {
+        rimplog::init_logger(LoggerBuilder {
+            file: Some(common::temp_path("missing-dir").join("app.log")),
+            ..builder()
+        });
+        log_info!("still logged");
+        return;
+    }
+    let output = run_child("unopenable_file_falls_back_to_stdout");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains(&format!("[ {} INFO]  still logged\n", TIMESTAMP)), "{}", stdout);
+    assert!(stderr.contains("logging to stdout instead"), "{}", stderr);
+    assert!(!stderr.contains("still logged"), "{}", stderr);
+}
+
+#[test]
+fn watch_level_file_changes_the_level() {
+    if is_child() {
+        let level_file = common::temp_path("level.txt");
+        let log_file = common::temp_path("watched.log");
+        fs::write(&level_file, "info\n").unwrap();
+        rimplog::init_logger(LoggerBuilder {
+            level: "info".to_string(),
+            file: Some(log_file.clone()),
+            watch_level_file: Some(level_file.clone()),
+            ..builder()
+        });
+        log_debug!("hidden");
+        fs::write(&level_file, "debug\n").unwrap();
+        let start = Instant::now();
+        while log::max_level() < log::LevelFilter::Debug && start.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(50));
+        }
+        log_debug!("shown");
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(level_file);
+        let _ = fs::remove_file(log_file);
+        assert_eq!(output, format!("[ {} DEBUG] shown\n", TIMESTAMP));
+        return;
+    }
+    assert_child_passes("watch_level_file_changes_the_level");
+}
+
+#[test]
+fn log_config_on_init_logs_the_effective_configuration() {
+    if is_child() {
+        let log_file = common::temp_path("config.log");
+        rimplog::init_logger(LoggerBuilder {
+            level: "debug".to_string(),
+            preset: rimplog::LoggerPreset::THREAD,
+            file: Some(log_file.clone()),
+            log_config_on_init: true,
+            ..builder()
+        });
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        let expected = format!(
+            "rimplog initialized: level=DEBUG, preset=THREAD, only_project_logs=false, RUST_LOG=unset, sink=file '{}'\n",
+            log_file.display()
+        );
+        assert_eq!(output.lines().count(), 1, "{}", output);
+        assert!(output.ends_with(&expected), "{}", output);
+        return;
+    }
+    assert_child_passes("log_config_on_init_logs_the_effective_configuration");
+}
+
+#[test]
+fn log_config_on_init_respects_the_level() {
+    if is_child() {
+        let log_file = common::temp_path("config-off.log");
+        rimplog::init_logger(LoggerBuilder {
+            level: "off".to_string(),
+            file: Some(log_file.clone()),
+            log_config_on_init: true,
+            ..builder()
+        });
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        assert_eq!(output, "");
+        return;
+    }
+    assert_child_passes("log_config_on_init_respects_the_level");
+}
+
+#[test]
+fn session_separator_marks_the_second_run() {
+    if is_child() {
+        rimplog::init_logger(LoggerBuilder {
+            file: Some(env::var("RIMPLOG_TEST_FILE").unwrap().into()),
+            session_separator: true,
+            ..builder()
+        });
+        log_info!("run {}", env::var("RIMPLOG_TEST_RUN").unwrap());
+        return;
+    }
+    let log_file = common::temp_path("sessions.log");
+    for run in ["1", "2"] {
+        let output = run_child_with_env(
+            "session_separator_marks_the_second_run",
+            &[("RIMPLOG_TEST_FILE", log_file.to_str().unwrap()), ("RIMPLOG_TEST_RUN", run)],
+        );
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let output = fs::read_to_string(&log_file).unwrap();
+    let _ = fs::remove_file(&log_file);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", output);
+    // The first run starts an empty file, so it has no separator
+    assert_eq!(lines[0], format!("[ {} INFO]  run 1", TIMESTAMP));
+    assert!(lines[1].starts_with("===== session started ") && lines[1].contains("(pid "), "{}", output);
+    assert_eq!(lines[2], format!("[ {} INFO]  run 2", TIMESTAMP));
+}
+
+#[test]
+fn file_header_and_footer_wrap_the_records() {
+    if is_child() {
+        let log_file = common::temp_path("framed.log");
+        let guard = rimplog::init_logger_with_guard(LoggerBuilder {
+            file: Some(log_file.clone()),
+            file_header: Some("# {name} started, pid {pid}".to_string()),
+            file_footer: Some("# {name} finished".to_string()),
+            ..builder()
+        });
+        log_info!("first");
+        log_info!("second");
+        assert_eq!(
+            fs::read_to_string(&log_file).unwrap(),
+            format!("# rimplog started, pid {1}\n[ {0} INFO]  first\n[ {0} INFO]  second\n", TIMESTAMP, std::process::id())
+        );
+        drop(guard);
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        assert_eq!(
+            output,
+            format!(
+                "# rimplog started, pid {1}\n[ {0} INFO]  first\n[ {0} INFO]  second\n# rimplog finished\n",
+                TIMESTAMP,
+                std::process::id()
+            )
+        );
+        return;
+    }
+    assert_child_passes("file_header_and_footer_wrap_the_records");
+}
+
+#[test]
+fn rimplog_color_decides_when_the_builder_does_not() {
+    if is_child() {
+        let log_file = common::temp_path("color.log");
+        rimplog::init_logger(LoggerBuilder {
+            file: Some(log_file.clone()),
+            color: None,
+            ..builder()
+        });
+        log_info!("ready");
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        // Reported to the parent, which knows the expected result
+        println!("colored={}", output.contains('\x1b'));
+        return;
+    }
+    // A file is not a terminal, so auto detection writes no colors
+    for (value, colored) in [("always", true), ("never", false), ("auto", false), ("sometimes", false)] {
+        let output = run_child_with_env("rimplog_color_decides_when_the_builder_does_not", &[("RIMPLOG_COLOR", value)]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(&format!("colored={}", colored)), "RIMPLOG_COLOR={}: {}", value, stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stderr.contains("Invalid RIMPLOG_COLOR 'sometimes'"), value == "sometimes", "{}", stderr);
+    }
+}
+
+#[test]
+fn stderr_level_splits_the_console_streams() {
+    if is_child() {
+        rimplog::init_logger(LoggerBuilder {
+            split_streams: true,
+            stderr_level: log::Level::Error,
+            ..builder()
+        });
+        log_warn!("disk almost full");
+        log_error!("disk full");
+        rimplog::flush();
+        return;
+    }
+    let output = run_child("stderr_level_splits_the_console_streams");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains(&format!("[ {} WARN]  disk almost full\n", TIMESTAMP)), "{}", stdout);
+    assert!(!stdout.contains("disk full\n"), "{}", stdout);
+    assert!(stderr.contains(&format!("[ {} ERROR] disk full\n", TIMESTAMP)), "{}", stderr);
+    assert!(!stderr.contains("disk almost full"), "{}", stderr);
+}
+
+#[test]
+fn level_source_decides_the_rust_log_precedence() {
+    if is_child() {
+        let level_source = match env::var("RIMPLOG_TEST_SOURCE").unwrap().as_str() {
+            "env_then_builder" => LevelSource::EnvThenBuilder,
+            "builder_only" => LevelSource::BuilderOnly,
+            _ => LevelSource::EnvOnly,
+        };
+        let log_file = common::temp_path("precedence.log");
+        rimplog::init_logger(LoggerBuilder {
+            level: "info".to_string(),
+            level_source,
+            warn_env_override: true,
+            file: Some(log_file.clone()),
+            ..builder()
+        });
+        log_debug!("debug from this module");
+        log::info!(target: "other", "info from another module\n");
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        print!("{}", output);
+        return;
+    }
+    // `RUST_LOG` sets a default level and a level for the module of this test
+    let run = |source| {
+        let output = run_child_with_env(
+            "level_source_decides_the_rust_log_precedence",
+            &[("RUST_LOG", "warn,init=debug"), ("RIMPLOG_TEST_SOURCE", source)],
+        );
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        (stdout.contains("debug from this module"), stdout.contains("info from another module"), stderr)
+    };
+
+    // The module directive of `RUST_LOG` wins, `level` replaces its default level
+    let (debug, info, stderr) = run("env_then_builder");
+    assert!(debug && info);
+    assert!(stderr.contains("RUST_LOG='warn,init=debug' is set and overrides level 'INFO' for the modules it names"), "{}", stderr);
+
+    let (debug, info, stderr) = run("builder_only");
+    assert!(!debug && info);
+    assert!(!stderr.contains("RUST_LOG="), "{}", stderr);
+
+    let (debug, info, stderr) = run("env_only");
+    assert!(debug && !info);
+    assert!(stderr.contains("RUST_LOG='warn,init=debug' is set and overrides level 'INFO'"), "{}", stderr);
+}
+
+#[test]
+fn effective_config_reflects_rust_log() {
+    if is_child() {
+        assert_eq!(rimplog::effective_config(), None);
+        let log_file = common::temp_path("effective.log");
+        rimplog::init_logger(LoggerBuilder {
+            level: "info".to_string(),
+            level_source: LevelSource::EnvOnly,
+            file: Some(log_file.clone()),
+            ..builder()
+        });
+        let _ = fs::remove_file(&log_file);
+        let config = rimplog::effective_config().unwrap();
+        assert_eq!(config.rust_log.as_deref(), Some("warn,init=trace"));
+        assert_eq!(config.level, log::LevelFilter::Info);
+        assert_eq!(config.max_level, log::LevelFilter::Trace);
+        assert_eq!(config.level_source, LevelSource::EnvOnly);
+        assert_eq!(config.sink, format!("file '{}'", log_file.display()));
+        assert_eq!(config.color, rimplog::ColorMode::Never);
+        return;
+    }
+    let output = run_child_with_env("effective_config_reflects_rust_log", &[("RUST_LOG", "warn,init=trace")]);
+    assert!(
+        output.status.success(),
+        "stdout:\n{}\nstderr:\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn prettify_json_messages_only_indents_the_console() {
+    if is_child() {
+        let routed = common::temp_path("json-db.log");
+        let debug = common::temp_path("json-debug.log");
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        rimplog::init_logger(LoggerBuilder {
+            prettify_json_messages: true,
+            target_files: vec![("app::db".to_string(), routed.clone())],
+            debug_file: Some((debug.clone(), log::LevelFilter::Trace)),
+            network_sink: Some(NetworkSink::Udp(receiver.local_addr().unwrap())),
+            sinks: vec![SinkConfig {
+                preset: LoggerPreset::RAW,
+                level: log::LevelFilter::Info,
+                target: SinkTarget::Writer(sink.clone()),
+            }],
+            ..builder()
+        });
+        log::info!(target: "app::db", "{{\"rows\":3}}\n");
+        rimplog::flush();
+
+        let compact = format!("[ {} INFO]  {{\"rows\":3}}\n", TIMESTAMP);
+        let mut datagram = [0; 1024];
+        let len = receiver.recv(&mut datagram).unwrap();
+        assert_eq!(String::from_utf8_lossy(&datagram[..len]), compact);
+        assert_eq!(fs::read_to_string(&routed).unwrap(), compact);
+        assert_eq!(fs::read_to_string(&debug).unwrap(), compact);
+        assert_eq!(String::from_utf8(sink.lock().unwrap().clone()).unwrap(), "{\"rows\":3}\n");
+        let _ = fs::remove_file(routed);
+        let _ = fs::remove_file(debug);
+        return;
+    }
+    let output = run_child("prettify_json_messages_only_indents_the_console");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains(&format!("[ {} INFO]  {{\n  \"rows\": 3\n}}\n", TIMESTAMP)), "{}", stderr);
+}
+
+#[test]
+fn racing_inits_install_exactly_one_logger() {
+    if is_child() {
+        let files: Vec<_> = (0..16).map(|i| common::temp_path(&format!("race-{}.log", i))).collect();
+        let barrier = Barrier::new(files.len());
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = files
+                .iter()
+                .map(|file| {
+                    let barrier = &barrier;
+                    scope.spawn(move || {
+                        let config = LoggerBuilder {
+                            file: Some(file.clone()),
+                            ..builder()
+                        };
+                        barrier.wait();
+                        rimplog::try_init_logger(config)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results.iter().all(|result| matches!(result, Ok(()) | Err(rimplog::AlreadyInitialized))));
+        // The losers don't even create their file
+        let created: Vec<_> = files.iter().filter(|file| file.exists()).collect();
+        assert_eq!(created.len(), 1, "{:?}", created);
+        let _ = fs::remove_file(created[0]);
+        return;
+    }
+    assert_child_passes("racing_inits_install_exactly_one_logger");
+}
+
+#[cfg(unix)]
+#[test]
+fn line_buffered_stdout_is_written_without_a_flush() {
+    if is_child() {
+        let stdout_buffering = match env::var("RIMPLOG_TEST_BUFFERING").unwrap().as_str() {
+            "line" => StdoutBuffering::Line,
+            _ => StdoutBuffering::Block,
+        };
+        rimplog::init_logger(LoggerBuilder {
+            split_streams: true,
+            stdout_buffering: Some(stdout_buffering),
+            ..builder()
+        });
+        log_info!("visible at once");
+        // Exit without running any destructor or flush
+        unsafe { libc::_exit(0) };
+    }
+    let stdout = |buffering| {
+        let output = run_child_with_env(
+            "line_buffered_stdout_is_written_without_a_flush",
+            &[("RIMPLOG_TEST_BUFFERING", buffering)],
+        );
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let line = format!("[ {} INFO]  visible at once\n", TIMESTAMP);
+    assert!(stdout("line").contains(&line));
+    // Block buffered lines wait for a flush, which never comes
+    assert!(!stdout("block").contains(&line));
+}
+
+#[cfg(unix)]
+#[test]
+fn capture_stderr_logs_lines_written_to_stderr() {
+    if is_child() {
+        let log_file = common::temp_path("captured.log");
+        rimplog::init_logger(LoggerBuilder {
+            file: Some(log_file.clone()),
+            capture_stderr: true,
+            ..builder()
+        });
+        // Written like a C library would, straight to file descriptor 2
+        let line = b"from a dependency\n";
+        assert_eq!(unsafe { libc::write(2, line.as_ptr().cast(), line.len()) }, line.len() as isize);
+        let expected = format!("[ {} WARN]  from a dependency\n", TIMESTAMP);
+        let start = Instant::now();
+        let mut output = String::new();
+        while output != expected && start.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(20));
+            output = fs::read_to_string(&log_file).unwrap();
+        }
+        let _ = fs::remove_file(&log_file);
+        assert_eq!(output, expected);
+        return;
+    }
+    let output = run_child("capture_stderr_logs_lines_written_to_stderr");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.contains("from a dependency"), "{}", stderr);
+}
+
+#[test]
+fn span_timing_summary_counts_the_runs_of_each_span() {
+    if is_child() {
+        let log_file = common::temp_path("timings.log");
+        let guard = rimplog::init_logger_with_guard(LoggerBuilder {
+            level: "info".to_string(),
+            file: Some(log_file.clone()),
+            span_timing_summary: true,
+            ..builder()
+        });
+        for _ in 0..2 {
+            let _span = rimplog::span!("parse");
+            thread::sleep(Duration::from_millis(30));
+        }
+        {
+            let _span = rimplog::span!("load");
+        }
+        drop(guard);
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", output);
+        assert_eq!(lines[0], format!("[ {} INFO]  span timings:", TIMESTAMP));
+        // The longest total first
+        assert!(lines[1].starts_with(&format!("[ {} INFO]    parse: 2 runs, total ", TIMESTAMP)), "{}", output);
+        assert!(lines[2].starts_with(&format!("[ {} INFO]    load: 1 runs, total ", TIMESTAMP)), "{}", output);
+        return;
+    }
+    assert_child_passes("span_timing_summary_counts_the_runs_of_each_span");
+}
+
+#[test]
+fn build_logger_can_be_installed_by_the_caller() {
+    if is_child() {
+        let log_file = common::temp_path("boxed.log");
+        let config = LoggerBuilder {
+            level: "debug".to_string(),
+            file: Some(log_file.clone()),
+            ..builder()
+        };
+        assert_eq!(rimplog::max_level(&config), log::LevelFilter::Debug);
+        log::set_max_level(rimplog::max_level(&config));
+        log::set_boxed_logger(rimplog::build_logger(config)).unwrap();
+        log_debug!("boxed");
+        log::logger().flush();
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(log_file);
+        assert_eq!(output, format!("[ {} DEBUG] boxed\n", TIMESTAMP));
+        // The boxed logger isn't the one rimplog installs
+        assert!(!rimplog::reconfigure(builder()));
+        return;
+    }
+    assert_child_passes("build_logger_can_be_installed_by_the_caller");
+}
+
+#[test]
+fn hyperlink_paths_wraps_the_path_in_an_osc_8_link() {
+    if is_child() {
+        let log_file = common::temp_path("hyperlink.log");
+        rimplog::init_logger(LoggerBuilder {
+            file: Some(log_file.clone()),
+            preset: rimplog::LoggerPreset::FULL,
+            color: Some(rimplog::ColorMode::Always),
+            hyperlink_paths: true,
+            hyperlink_url: "vscode://file/{path}:{line}".to_string(),
+            ..builder()
+        });
+        let line = line!() + 1;
+        log_info!("ready");
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        let path = env::current_dir().unwrap().join(file!());
+        let link = format!("\x1b]8;;vscode://file/{}:{}\x1b\\", path.display(), line);
+        if env::var("FORCE_HYPERLINK").unwrap() == "1" {
+            let start = output.find(&link).unwrap_or_else(|| panic!("no link in {:?}", output));
+            let end = output.find("\x1b]8;;\x1b\\").unwrap_or_else(|| panic!("unterminated link in {:?}", output));
+            // The link text is the colored `file:line`
+            assert!(output[start + link.len()..end].contains(&line.to_string()), "{:?}", output);
+        } else {
+            assert!(!output.contains("\x1b]8"), "{:?}", output);
+        }
+        return;
+    }
+    for force in ["1", "0"] {
+        let output = run_child_with_env("hyperlink_paths_wraps_the_path_in_an_osc_8_link", &[("FORCE_HYPERLINK", force)]);
+        assert!(
+            output.status.success(),
+            "FORCE_HYPERLINK={}\n{}",
+            force,
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+#[test]
+fn reconfigure_swaps_the_preset_without_redoing_init() {
+    if is_child() {
+        let log_file = common::temp_path("swapped.log");
+        fs::write(&log_file, "earlier run\n").unwrap();
+        let framed = LoggerBuilder {
+            file: Some(log_file.clone()),
+            session_separator: true,
+            file_header: Some("# header".to_string()),
+            file_footer: Some("# footer".to_string()),
+            ..builder()
+        };
+        let guard = rimplog::init_logger_with_guard(LoggerBuilder {
+            preset: rimplog::LoggerPreset::THREAD,
+            color: Some(rimplog::ColorMode::Always),
+            ..framed.clone()
+        });
+        assert!(colored::control::SHOULD_COLORIZE.should_colorize());
+        log_info!("threaded");
+        assert!(rimplog::reconfigure(LoggerBuilder {
+            preset: rimplog::LoggerPreset::SIMPLE,
+            color: None,
+            ..framed
+        }));
+        // The override of the first configuration is gone, and stdout is not a terminal here
+        assert!(!colored::control::SHOULD_COLORIZE.should_colorize());
+        log_info!("simple");
+        drop(guard);
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 7, "{:?}", output);
+        assert_eq!(lines[0], "earlier run");
+        assert!(lines[1].starts_with("===== session started "), "{:?}", output);
+        assert_eq!(lines[2], "# header");
+        assert!(lines[3].contains('\x1b') && lines[3].contains("threaded"), "{:?}", output);
+        // The footer of the replaced configuration, and no second separator or header
+        assert_eq!(lines[4], "# footer");
+        assert_eq!(lines[5], format!("[ {} INFO]  simple", TIMESTAMP));
+        assert_eq!(lines[6], "# footer");
+        return;
+    }
+    assert_child_passes("reconfigure_swaps_the_preset_without_redoing_init");
+}
+
+#[test]
+fn exit_summary_counts_the_records_of_each_level() {
+    if is_child() {
+        let log_file = common::temp_path("summary.log");
+        let guard = rimplog::init_logger_with_guard(LoggerBuilder {
+            level: "info".to_string(),
+            file: Some(log_file.clone()),
+            exit_summary: Some("summary: errors={errors} warnings={warnings} infos={infos} duration={duration}".to_string()),
+            ..builder()
+        });
+        for _ in 0..3 {
+            log_error!("failed");
+        }
+        log_warn!("slow");
+        log_warn!("slow again");
+        log_info!("done");
+        // Filtered out, so not counted
+        log_debug!("details");
+        drop(guard);
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        let last = output.lines().last().unwrap();
+        let prefix = format!("[ {} INFO]  summary: errors=3 warnings=2 infos=1 duration=", TIMESTAMP);
+        let duration = last.strip_prefix(&prefix).unwrap_or_else(|| panic!("{:?}", output));
+        assert!(duration.ends_with('s') && duration.trim_end_matches('s').parse::<f64>().is_ok(), "{:?}", output);
+        assert_eq!(output.lines().count(), 7, "{:?}", output);
+        return;
+    }
+    assert_child_passes("exit_summary_counts_the_records_of_each_level");
+}
+
+#[test]
+fn max_allowed_level_caps_rust_log() {
+    if is_child() {
+        let log_file = common::temp_path("capped.log");
+        rimplog::init_logger(LoggerBuilder {
+            level_source: LevelSource::EnvOnly,
+            max_allowed_level: Some(log::LevelFilter::Info),
+            file: Some(log_file.clone()),
+            ..builder()
+        });
+        assert_eq!(log::max_level(), log::LevelFilter::Info);
+        rimplog::log_trace!("secret");
+        log_debug!("secret");
+        log_info!("allowed");
+        // Runtime changes are capped too
+        rimplog::set_max_level(log::LevelFilter::Trace);
+        assert_eq!(log::max_level(), log::LevelFilter::Info);
+        rimplog::log_trace!("secret");
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        assert_eq!(output, format!("[ {} INFO]  allowed\n", TIMESTAMP));
+        return;
+    }
+    let output = run_child_with_env("max_allowed_level_caps_rust_log", &[("RUST_LOG", "trace")]);
+    assert!(
+        output.status.success(),
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn static_config_initializes_the_logger() {
+    use rimplog::{ColorMode, StaticConfig};
+
+    static CONFIG: StaticConfig = StaticConfig::SIMPLE_INFO
+        .level(log::LevelFilter::Debug)
+        .color(ColorMode::Never)
+        .level_source(LevelSource::BuilderOnly);
+
+    if is_child() {
+        let log_file = common::temp_path("static.log");
+        let config = LoggerBuilder {
+            file: Some(log_file.clone()),
+            ..CONFIG.builder().clock(common::clock())
+        };
+        assert_eq!(rimplog::max_level(&config), log::LevelFilter::Debug);
+        rimplog::init_logger(config);
+        log_debug!("from a static");
+        log::trace!("filtered out");
+        log::logger().flush();
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(log_file);
+        assert_eq!(output, format!("[ {} DEBUG] from a static\n", TIMESTAMP));
+        return;
+    }
+    assert_child_passes("static_config_initializes_the_logger");
+}
+
+#[test]
+fn hidden_external_records_reach_no_output_or_counter() {
+    if is_child() {
+        let log_file = common::temp_path("hidden.log");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mirrored = Arc::new(Mutex::new(Vec::new()));
+        let mirror = Arc::clone(&mirrored);
+        let guard = rimplog::init_logger_with_guard(LoggerBuilder {
+            file: Some(log_file.clone()),
+            external_detail: rimplog::ExternalDetail::Hidden,
+            channel_sink: Some(sender),
+            record_mirror: Some(Arc::new(move |record: &log::Record| {
+                mirror.lock().unwrap().push(record.target().to_string());
+            })),
+            exit_summary: Some("summary: errors={errors} infos={infos}".to_string()),
+            ..builder()
+        });
+        assert!(!log::log_enabled!(target: "hyper::proto", log::Level::Error));
+        assert!(log::log_enabled!(target: "rimplog::app", log::Level::Error));
+        log::error!(target: "hyper::proto", "connection reset\n");
+        log::info!(target: "rimplog::app", "ready\n");
+        drop(guard);
+        let output = fs::read_to_string(&log_file).unwrap();
+        let _ = fs::remove_file(&log_file);
+        assert_eq!(
+            output,
+            format!("[ {0} INFO]  ready\n[ {0} INFO]  summary: errors=0 infos=1\n", TIMESTAMP)
+        );
+        let targets: Vec<_> = receiver.try_iter().map(|event| event.target).collect();
+        assert_eq!(targets, ["rimplog::app", "rimplog"]);
+        assert_eq!(*mirrored.lock().unwrap(), ["rimplog::app", "rimplog"]);
+        return;
+    }
+    assert_child_passes("hidden_external_records_reach_no_output_or_counter");
+}
+
+#[cfg(unix)]
+#[test]
+fn stdout_buffering_applies_to_sinks_and_the_file_fallback() {
+    if is_child() {
+        let (file, sinks) = match env::var("RIMPLOG_TEST_TARGET").unwrap().as_str() {
+            "sink" => (
+                None,
+                vec![SinkConfig {
+                    preset: LoggerPreset::SIMPLE,
+                    level: log::LevelFilter::Info,
+                    target: SinkTarget::Stdout,
+                }],
+            ),
+            // An unopenable file, so records go to stdout
+            _ => (Some(env::temp_dir().join("rimplog-missing-dir").join("app.log")), Vec::new()),
+        };
+        let stdout_buffering = match env::var("RIMPLOG_TEST_BUFFERING").unwrap().as_str() {
+            "line" => StdoutBuffering::Line,
+            _ => StdoutBuffering::Block,
+        };
+        rimplog::init_logger(LoggerBuilder {
+            file,
+            sinks,
+            stdout_buffering: Some(stdout_buffering),
+            ..builder()
+        });
+        log_info!("visible at once");
+        // Exit without running any destructor or flush
+        unsafe { libc::_exit(0) };
+    }
+    let stdout = |target, buffering| {
+        let output = run_child_with_env(
+            "stdout_buffering_applies_to_sinks_and_the_file_fallback",
+            &[("RIMPLOG_TEST_TARGET", target), ("RIMPLOG_TEST_BUFFERING", buffering)],
+        );
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let line = format!("[ {} INFO]  visible at once\n", TIMESTAMP);
+    for target in ["sink", "fallback"] {
+        assert!(stdout(target, "line").contains(&line), "{}", target);
+        assert!(!stdout(target, "block").contains(&line), "{}", target);
+    }
+}
@@ -0,0 +1,709 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::LevelFilter;
+use rimplog::{log_debug, log_info, log_trace, ColorMode, LoggerBuilder, LoggerPreset, SinkConfig, SinkTarget};
+
+use common::{builder, capture, TIMESTAMP};
+
+#[test]
+fn flush_writes_out_buffered_sinks() {
+    let path = common::temp_path("buffered.log");
+    let buffered = BufWriter::new(File::create(&path).unwrap());
+    let config = LoggerBuilder {
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::SIMPLE,
+            level: LevelFilter::Info,
+            target: SinkTarget::Writer(Arc::new(Mutex::new(buffered))),
+        }],
+        ..builder()
+    };
+    capture(config, || {
+        log_info!("checkpoint");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        rimplog::flush();
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("[ {} INFO]  checkpoint\n", TIMESTAMP));
+    });
+    let _ = fs::remove_file(path);
+}
+
+#[cfg(feature = "redact")]
+#[test]
+fn redactor_applies_to_every_sink() {
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let config = LoggerBuilder {
+        redactor: Some(rimplog::card_number_redactor()),
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::RAW,
+            level: LevelFilter::Info,
+            target: SinkTarget::Writer(sink.clone()),
+        }],
+        ..builder()
+    };
+    let output = capture(config, || log_info!("charged 4111111111111111"));
+    assert_eq!(output, format!("[ {} INFO]  charged ************1111\n", TIMESTAMP));
+    assert_eq!(String::from_utf8(sink.lock().unwrap().clone()).unwrap(), "charged ************1111\n");
+}
+
+#[test]
+fn target_files_route_records_by_target_prefix() {
+    let db = common::temp_path("db.log");
+    let http = common::temp_path("http.log");
+    let routed = |target_files_only| {
+        let config = LoggerBuilder {
+            target_files: vec![("app::db".to_string(), db.clone()), ("app::http".to_string(), http.clone())],
+            target_files_only,
+            ..builder()
+        };
+        let main = capture(config, || {
+            log::info!(target: "app::db::pool", "query\n");
+            log::info!(target: "app::jobs", "tick\n");
+        });
+        let (db_output, http_output) = (fs::read_to_string(&db).unwrap(), fs::read_to_string(&http).unwrap());
+        let _ = fs::remove_file(&db);
+        let _ = fs::remove_file(&http);
+        (main, db_output, http_output)
+    };
+
+    let (main, db_output, http_output) = routed(false);
+    assert_eq!(db_output, format!("[ {} INFO]  query\n", TIMESTAMP));
+    assert_eq!(http_output, "");
+    assert_eq!(main, format!("[ {0} INFO]  query\n[ {0} INFO]  tick\n", TIMESTAMP));
+
+    let (main, db_output, http_output) = routed(true);
+    assert_eq!(db_output, format!("[ {} INFO]  query\n", TIMESTAMP));
+    assert_eq!(http_output, "");
+    assert_eq!(main, format!("[ {} INFO]  tick\n", TIMESTAMP));
+}
+
+#[cfg(feature = "kv")]
+#[test]
+fn sort_kv_orders_the_pairs_by_key() {
+    let log = || log::info!(zone = "eu", attempt = 2, user = 42, id = "a1"; "login\n");
+    let output = capture(builder(), log);
+    assert_eq!(output, format!("[ {} INFO]  login zone=eu attempt=2 user=42 id=a1\n", TIMESTAMP));
+
+    let config = LoggerBuilder {
+        sort_kv: true,
+        ..builder()
+    };
+    let output = capture(config, log);
+    assert_eq!(output, format!("[ {} INFO]  login attempt=2 id=a1 user=42 zone=eu\n", TIMESTAMP));
+}
+
+#[test]
+fn set_max_level_raises_the_level_at_runtime() {
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_debug!("hidden");
+        rimplog::set_max_level(LevelFilter::Debug);
+        assert_eq!(log::max_level(), LevelFilter::Debug);
+        log_debug!("shown");
+        log_trace!("still hidden");
+    });
+    assert_eq!(output, format!("[ {} DEBUG] shown\n", TIMESTAMP));
+}
+
+#[test]
+fn trace_records_pass_the_facade_filter() {
+    let output = capture(builder(), || {
+        assert_eq!(log::max_level(), LevelFilter::Trace);
+        log_trace!("deepest");
+    });
+    assert_eq!(output, format!("[ {} TRACE] deepest\n", TIMESTAMP));
+
+    // A sink more verbose than the main level raises the facade level too
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let config = LoggerBuilder {
+        level: "warn".to_string(),
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::RAW,
+            level: LevelFilter::Trace,
+            target: SinkTarget::Writer(sink.clone()),
+        }],
+        ..builder()
+    };
+    let output = capture(config, || {
+        assert_eq!(log::max_level(), LevelFilter::Trace);
+        log_trace!("deepest");
+    });
+    assert_eq!(output, "");
+    assert_eq!(String::from_utf8(sink.lock().unwrap().clone()).unwrap(), "deepest\n");
+}
+
+#[test]
+fn show_delta_measures_the_time_since_the_previous_record() {
+    let config = LoggerBuilder {
+        show_delta: true,
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_info!("first");
+        thread::sleep(Duration::from_millis(150));
+        log_info!("second");
+    });
+    let deltas: Vec<u64> = output
+        .lines()
+        .map(|line| {
+            let delta = line.split_once(" +").and_then(|(_, rest)| rest.split_once("ms ")).map(|(delta, _)| delta);
+            delta.unwrap_or_else(|| panic!("no delta in {:?}", line)).parse().unwrap()
+        })
+        .collect();
+    assert_eq!(deltas.len(), 2, "{}", output);
+    assert_eq!(deltas[0], 0);
+    assert!((150..5000).contains(&deltas[1]), "{}", output);
+    assert!(output.starts_with(&format!("[ {} +0ms INFO]  first\n", TIMESTAMP)), "{}", output);
+}
+
+#[test]
+fn progress_aware_clears_the_line_and_redraws_after_each_record() {
+    let redraws = Arc::new(AtomicUsize::new(0));
+    let counter = redraws.clone();
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        progress_aware: true,
+        progress_redraw: Some(Arc::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })),
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_info!("downloading");
+        log_debug!("filtered out");
+    });
+    // Without colors the escapes are stripped, as for a file, and only the carriage return is left
+    assert_eq!(output, format!("\r[ {} INFO]  downloading\n", TIMESTAMP));
+    assert_eq!(redraws.load(Ordering::SeqCst), 1);
+
+    let config = LoggerBuilder {
+        progress_aware: true,
+        color: Some(ColorMode::Always),
+        ..builder()
+    };
+    let output = capture(config, || log_info!("downloading"));
+    assert!(output.starts_with("\r\x1b[2K"), "{:?}", output);
+    assert_eq!(output.matches("\x1b[2K").count(), 1, "{:?}", output);
+    assert!(output.contains("downloading"), "{:?}", output);
+}
+
+#[test]
+fn line_prefix_and_suffix_wrap_every_line_of_every_sink() {
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let config = LoggerBuilder {
+        line_prefix: Some("shard-3 | ".to_string()),
+        line_suffix: Some(" |".to_string()),
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::RAW,
+            level: LevelFilter::Info,
+            target: SinkTarget::Writer(sink.clone()),
+        }],
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_info!("ready");
+        log_info!("two\nlines");
+    });
+    assert_eq!(
+        output,
+        format!(
+            "shard-3 | [ {0} INFO]  ready |\nshard-3 | [ {0} INFO]  two |\nshard-3 | lines |\n",
+            TIMESTAMP
+        )
+    );
+    assert_eq!(
+        String::from_utf8(sink.lock().unwrap().clone()).unwrap(),
+        "shard-3 | ready |\nshard-3 | two |\nshard-3 | lines |\n"
+    );
+}
+
+#[test]
+fn debug_file_gets_records_below_the_main_level() {
+    let debug = common::temp_path("debug.log");
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        debug_file: Some((debug.clone(), LevelFilter::Trace)),
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_trace!("cache miss");
+        log_info!("request done");
+    });
+    let debug_output = fs::read_to_string(&debug).unwrap();
+    let _ = fs::remove_file(&debug);
+    assert_eq!(output, format!("[ {} INFO]  request done\n", TIMESTAMP));
+    assert_eq!(debug_output, format!("[ {0} TRACE] cache miss\n[ {0} INFO]  request done\n", TIMESTAMP));
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_sink_receives_lines_without_colors() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    let path = common::temp_path("rimplog.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let config = LoggerBuilder {
+        network_sink: Some(rimplog::NetworkSink::UnixSocket(path.clone())),
+        color: Some(ColorMode::Always),
+        ..builder()
+    };
+    capture(config, || log_info!("shipped"));
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    let _ = fs::remove_file(&path);
+    assert_eq!(line, format!("[ {} INFO]  shipped\n", TIMESTAMP));
+}
+
+#[test]
+fn burst_policy_keeps_the_first_records_then_samples() {
+    let config = LoggerBuilder {
+        burst_policy: Some(rimplog::BurstPolicy {
+            initial: 2,
+            then_every: 3,
+        }),
+        ..builder()
+    };
+    let output = capture(config, || {
+        for i in 0..8 {
+            log_info!("tick {}", i);
+        }
+        log::info!(target: "other", "tock\n");
+    });
+    assert_eq!(
+        output,
+        format!(
+            "[ {0} INFO]  tick 0\n[ {0} INFO]  tick 1\n[ {0} INFO]  tick 2\n[ {0} INFO]  tick 5\n[ {0} INFO]  tock\n",
+            TIMESTAMP
+        )
+    );
+}
+
+#[test]
+fn sinks_format_with_their_own_preset_and_level() {
+    let full = Arc::new(Mutex::new(Vec::new()));
+    let raw = Arc::new(Mutex::new(Vec::new()));
+    let config = LoggerBuilder {
+        sinks: vec![
+            SinkConfig {
+                preset: LoggerPreset::THREAD,
+                level: LevelFilter::Debug,
+                target: SinkTarget::Writer(full.clone()),
+            },
+            SinkConfig {
+                preset: LoggerPreset::RAW,
+                level: LevelFilter::Info,
+                target: SinkTarget::Writer(raw.clone()),
+            },
+        ],
+        ..builder()
+    };
+    let output = capture(config, || {
+        thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(|| {
+                log_debug!("connecting");
+                log_info!("connected");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    });
+    let read = |sink: &Arc<Mutex<Vec<u8>>>| String::from_utf8(sink.lock().unwrap().clone()).unwrap();
+    assert_eq!(output, format!("[ {0} DEBUG] connecting\n[ {0} INFO]  connected\n", TIMESTAMP));
+    assert_eq!(
+        read(&full),
+        format!("{0} DEBUG [worker] connecting\n{0} INFO  [worker] connected\n", TIMESTAMP)
+    );
+    assert_eq!(read(&raw), "connected\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn show_cpu_time_increases_with_cpu_bound_work() {
+    let config = LoggerBuilder {
+        show_cpu_time: true,
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_info!("before");
+        let start = std::time::Instant::now();
+        let mut x = 0u64;
+        while start.elapsed() < Duration::from_millis(200) {
+            x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+        }
+        log_info!("after");
+    });
+    let cpu_times: Vec<f64> = output
+        .lines()
+        .map(|line| {
+            let cpu_time = line.split_once(" cpu=").and_then(|(_, rest)| rest.split_once("s ")).map(|(cpu_time, _)| cpu_time);
+            cpu_time.unwrap_or_else(|| panic!("no CPU time in {:?}", line)).parse().unwrap()
+        })
+        .collect();
+    assert_eq!(cpu_times.len(), 2, "{}", output);
+    // Most of the 200ms were spent on the CPU
+    assert!(cpu_times[1] - cpu_times[0] >= 0.1, "{}", output);
+}
+
+#[test]
+fn raw_preset_still_filters_by_level() {
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        ..builder().preset(LoggerPreset::RAW)
+    };
+    let output = capture(config, || {
+        log_debug!("hidden");
+        log_info!("shown  ");
+    });
+    assert_eq!(output, "shown  \n");
+}
+
+#[cfg(feature = "indicatif")]
+#[test]
+fn indicatif_suspend_writes_records_while_the_bars_are_hidden() {
+    use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, TermLike};
+
+    /// A terminal recording what is drawn on it, next to the records
+    #[derive(Debug, Clone)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn push(&self, event: String) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(event);
+            Ok(())
+        }
+    }
+
+    impl TermLike for Recorder {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, n: usize) -> std::io::Result<()> {
+            self.push(format!("up {}", n))
+        }
+        fn move_cursor_down(&self, _: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> std::io::Result<()> {
+            self.push(format!("bar {}", s))
+        }
+        fn write_str(&self, s: &str) -> std::io::Result<()> {
+            self.push(format!("bar {}", s))
+        }
+        fn clear_line(&self) -> std::io::Result<()> {
+            self.push("clear".to_string())
+        }
+        fn flush(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.push(format!("record {}", String::from_utf8_lossy(buf)))?;
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let recorder = Recorder(Arc::new(Mutex::new(Vec::new())));
+    let bars = MultiProgress::with_draw_target(ProgressDrawTarget::term_like(Box::new(recorder.clone())));
+    let bar = bars.add(ProgressBar::new(10));
+    bar.set_style(indicatif::ProgressStyle::with_template("{pos}/{len}").unwrap());
+    bar.inc(3);
+    let config = LoggerBuilder {
+        progress_suspend: Some(rimplog::indicatif_suspend(&bars)),
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::RAW,
+            level: LevelFilter::Info,
+            target: SinkTarget::Writer(Arc::new(Mutex::new(recorder.clone()))),
+        }],
+        ..builder()
+    };
+    recorder.0.lock().unwrap().clear();
+    capture(config, || log_info!("checkpoint"));
+    let events = recorder.0.lock().unwrap().clone();
+    let record = events.iter().position(|event| event == "record checkpoint\n").unwrap_or_else(|| panic!("{:?}", events));
+    // The bar's line is cleared before the record, and the bar drawn again after it
+    assert!(events[..record].iter().any(|event| event == "clear"), "{:?}", events);
+    assert!(!events[..record].iter().any(|event| event.starts_with("bar ")), "{:?}", events);
+    assert!(events[record + 1..].iter().any(|event| event == "bar 3/10"), "{:?}", events);
+}
+
+#[test]
+fn concurrent_records_are_written_whole() {
+    const THREADS: usize = 16;
+    const RECORDS: usize = 500;
+    let routed = common::temp_path("routed.log");
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let config = LoggerBuilder {
+        target_files: vec![("stress".to_string(), routed.clone())],
+        sinks: vec![SinkConfig {
+            preset: LoggerPreset::SIMPLE,
+            level: LevelFilter::Info,
+            target: SinkTarget::Writer(sink.clone()),
+        }],
+        ..builder()
+    };
+    let output = capture(config, || {
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                scope.spawn(move || {
+                    for record in 0..RECORDS {
+                        // Long enough for some records to need several writes if they weren't buffered
+                        let padding = "x".repeat(record % 7 * 300);
+                        log::info!(target: "stress", "t{} r{} {}\n", thread, record, padding);
+                    }
+                });
+            }
+        });
+    });
+    let routed_output = fs::read_to_string(&routed).unwrap();
+    let _ = fs::remove_file(&routed);
+    let sink_output = String::from_utf8(sink.lock().unwrap().clone()).unwrap();
+    let prefix = format!("[ {} INFO]  t", TIMESTAMP);
+    for (name, output) in [("file", &output), ("target file", &routed_output), ("sink", &sink_output)] {
+        assert!(output.ends_with('\n'), "{} doesn't end with a newline", name);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), THREADS * RECORDS, "{}", name);
+        for line in lines {
+            assert!(line.starts_with(&prefix), "malformed {} line {:?}", name, line);
+            let (_, rest) = line.split_once(" r").unwrap_or_else(|| panic!("malformed {} line {:?}", name, line));
+            let (record, padding) = rest.split_once(' ').unwrap_or((rest, ""));
+            let record: usize = record.parse().unwrap_or_else(|_| panic!("malformed {} line {:?}", name, line));
+            assert_eq!(padding, "x".repeat(record % 7 * 300), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn level_remap_lowers_and_raises_levels() {
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        level_remap: vec![
+            ("logger".to_string(), log::Level::Warn, log::Level::Debug),
+            ("logger".to_string(), log::Level::Debug, log::Level::Error),
+        ],
+        ..builder()
+    };
+    assert_eq!(rimplog::max_level(&config), LevelFilter::Debug);
+    let output = capture(config, || {
+        // Debug records pass the facade since they are raised
+        assert_eq!(log::max_level(), LevelFilter::Debug);
+        rimplog::log_warn!("benign");
+        log_debug!("important");
+        log_trace!("hidden");
+        log::debug!(target: "other", "hidden\n");
+    });
+    // The demoted warning isn't remapped again to an error
+    assert_eq!(output, format!("[ {} ERROR] important\n", TIMESTAMP));
+}
+
+#[test]
+fn level_remap_applies_once_before_the_burst_policy() {
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        level_remap: vec![
+            ("logger".to_string(), log::Level::Warn, log::Level::Debug),
+            ("logger".to_string(), log::Level::Debug, log::Level::Warn),
+        ],
+        burst_policy: Some(rimplog::BurstPolicy {
+            initial: 1,
+            then_every: 0,
+        }),
+        ..builder()
+    };
+    let output = capture(config, || {
+        // Demoted below the level, so it doesn't use up the burst
+        rimplog::log_warn!("demoted");
+        log_info!("kept");
+        log_info!("sampled out");
+    });
+    assert_eq!(output, format!("[ {} INFO]  kept\n", TIMESTAMP));
+}
+
+#[cfg(feature = "tracing-mirror")]
+#[test]
+fn tracing_mirror_emits_an_event_next_to_the_output() {
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Collects the level and fields of every event
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for Events {
+        fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+            let mut fields = Fields(event.metadata().level().to_string());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+
+    let events = Events::default();
+    let subscriber = tracing_subscriber::registry().with(events.clone());
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        ..builder()
+    };
+    let mut line = 0;
+    let output = tracing::subscriber::with_default(subscriber, || {
+        capture(config, || {
+            line = line!() + 1;
+            rimplog::log_warn!("disk {}% full", 91);
+            log_debug!("filtered out");
+        })
+    });
+    assert_eq!(output, format!("[ {} WARN]  disk 91% full\n", TIMESTAMP));
+    assert_eq!(
+        *events.0.lock().unwrap(),
+        [format!(
+            "WARN message=disk 91% full log.target=\"logger\" log.file=\"tests/logger.rs\" log.line={}",
+            line
+        )]
+    );
+}
+
+#[test]
+fn channel_sink_receives_the_written_records() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        channel_sink: Some(sender),
+        ..builder()
+    };
+    let mut line = 0;
+    let output = capture(config, || {
+        line = line!() + 1;
+        log_info!("{} jobs queued", 3);
+        log_debug!("filtered out");
+    });
+    assert_eq!(output, format!("[ {} INFO]  3 jobs queued\n", TIMESTAMP));
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(
+        events,
+        [rimplog::LogEvent {
+            level: log::Level::Info,
+            target: "logger".to_string(),
+            file: Some("tests/logger.rs".to_string()),
+            line: Some(line),
+            message: "3 jobs queued".to_string(),
+            timestamp: chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:00+08:00").unwrap(),
+        }]
+    );
+}
+
+#[test]
+fn condense_timestamp_blanks_the_repeated_second() {
+    let config = LoggerBuilder {
+        condense_timestamp: true,
+        ..builder()
+    };
+    let next_second = chrono::DateTime::parse_from_rfc3339("2024-10-18T09:30:01+08:00").unwrap();
+    let output = capture(config, || {
+        log_info!("first");
+        log_info!("second");
+        rimplog::log_info_at_time!(next_second, "third");
+    });
+    assert_eq!(
+        output,
+        format!(
+            "[ {} INFO]  first\n[ {} INFO]  second\n[ 2024-10-18 09:30:01 INFO]  third\n",
+            TIMESTAMP,
+            " ".repeat(TIMESTAMP.len())
+        )
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_preset_keeps_a_multi_line_message_in_one_object() {
+    let config = LoggerBuilder {
+        multiline: true,
+        ..builder().preset(LoggerPreset::JSON)
+    };
+    let output = capture(config, || {
+        log_info!("query failed:\n  SELECT 1\n  timeout");
+        log_info!("next");
+    });
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 2, "{:?}", output);
+    let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(record["message"], "query failed:\n  SELECT 1\n  timeout");
+    assert_eq!(record["level"], "INFO");
+    assert_eq!(record["target"], "logger");
+    assert_eq!(record["timestamp"], TIMESTAMP);
+    let record: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+    assert_eq!(record["message"], "next");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn target_regex_filters_before_every_output() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let config = LoggerBuilder {
+        target_regex: Some("^app::(db|cache)".to_string()),
+        channel_sink: Some(sender),
+        ..builder()
+    };
+    let output = capture(config, || {
+        assert!(log::log_enabled!(target: "app::db", log::Level::Info));
+        assert!(!log::log_enabled!(target: "app::http", log::Level::Error));
+        log::info!(target: "app::db", "pool ready\n");
+        log::error!(target: "app::http", "bind failed\n");
+    });
+    assert_eq!(output, format!("[ {} INFO]  pool ready\n", TIMESTAMP));
+    let targets: Vec<_> = receiver.try_iter().map(|event| event.target).collect();
+    assert_eq!(targets, ["app::db"]);
+}
+
+#[test]
+fn record_mirror_can_log_and_change_the_level() {
+    let config = LoggerBuilder {
+        record_mirror: Some(Arc::new(|record: &log::Record| {
+            if record.args().to_string() == "outer\n" {
+                // Both need the logger, which mustn't be locked while the mirror runs
+                rimplog::set_max_level(LevelFilter::Debug);
+                log::info!("from the mirror\n");
+            }
+        })),
+        ..builder()
+    };
+    let (done, finished) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let output = capture(config, || {
+            log_info!("outer");
+            log_trace!("filtered out");
+        });
+        let _ = done.send(output);
+    });
+    let output = finished.recv_timeout(Duration::from_secs(10)).expect("the mirror deadlocked the logger");
+    assert_eq!(output, format!("[ {0} INFO]  from the mirror\n[ {0} INFO]  outer\n", TIMESTAMP));
+}
@@ -0,0 +1,362 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use log::Level;
+use rimplog::{
+    clog_info, log_assert, log_assert_panic, log_dbg, log_debug_lazy, log_err, log_info, log_info_at_time, log_info_lazy, log_line, log_throughput, span, LogErrExt,
+    LoggerBuilder,
+};
+
+use common::{builder, capture, TIMESTAMP};
+
+#[test]
+fn log_err_logs_the_error_once_and_returns_the_result() {
+    let mut failed = None;
+    let mut passed = None;
+    let output = capture(builder(), || {
+        failed = Some(log_err!("4x".parse::<u32>()));
+        passed = Some(log_err!(Level::Warn, "42".parse::<u32>()));
+    });
+    assert_eq!(output, format!("[ {} ERROR] invalid digit found in string\n", TIMESTAMP));
+    assert_eq!(failed.unwrap().unwrap_err(), "4x".parse::<u32>().unwrap_err());
+    assert_eq!(passed.unwrap(), Ok(42));
+}
+
+#[test]
+fn log_err_ext_logs_at_the_given_level() {
+    let mut result = None;
+    let output = capture(builder(), || {
+        result = Some("".parse::<u32>().log_err(Level::Warn));
+        let _ = "7".parse::<u32>().log_err(Level::Warn);
+    });
+    assert_eq!(output, format!("[ {} WARN]  cannot parse integer from empty string\n", TIMESTAMP));
+    assert!(result.unwrap().is_err());
+}
+
+#[test]
+fn log_err_targets_of_the_macro_and_the_trait() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let config = LoggerBuilder {
+        channel_sink: Some(sender),
+        ..builder()
+    };
+    let mut line = 0;
+    capture(config, || {
+        line = line!() + 1;
+        let _ = log_err!("a".parse::<u32>());
+        let _ = "b".parse::<u32>().log_err(Level::Error);
+        let _ = "c".parse::<u32>().log_err_to(module_path!(), Level::Error);
+    });
+    let events: Vec<_> = receiver.try_iter().map(|event| (event.target, event.file, event.line)).collect();
+    let file = Some("tests/macros.rs".to_string());
+    assert_eq!(
+        events,
+        [
+            // The macro sees the calling module, the trait only the crate unless it is given the module
+            ("macros".to_string(), file.clone(), Some(line)),
+            ("rimplog".to_string(), file.clone(), Some(line + 1)),
+            ("macros".to_string(), file, Some(line + 2)),
+        ]
+    );
+}
+
+#[test]
+fn nested_spans_indent_the_messages_logged_inside() {
+    let unpadded = LoggerBuilder {
+        pad_level: false,
+        ..builder()
+    };
+    let output = capture(unpadded, || {
+        let _outer = span!("outer");
+        log_info!("in outer");
+        {
+            let _inner = span!("inner");
+            log_info!("in inner");
+        }
+        log_info!("back in outer");
+    });
+    let messages: Vec<&str> = output.lines().map(|line| line.split_once("] ").unwrap().1).collect();
+    assert_eq!(
+        messages,
+        [
+            "-> outer",
+            "  in outer",
+            "  -> inner",
+            "    in inner",
+            "  <- inner",
+            "  back in outer",
+            "<- outer",
+        ]
+    );
+}
+
+#[test]
+fn log_assert_logs_failures_and_returns_the_condition() {
+    let cache_size = 0;
+    let mut results = Vec::new();
+    let output = capture(builder(), || {
+        results.push(log_assert!(cache_size == 0));
+        results.push(log_assert!(cache_size > 0));
+        results.push(log_assert!(cache_size > 0, "cache has {} entries", cache_size));
+    });
+    assert_eq!(results, [true, false, false]);
+    assert_eq!(
+        output,
+        format!(
+            "[ {0} ERROR] assertion failed: cache_size > 0\n[ {0} ERROR] assertion failed: cache_size > 0: cache has 0 entries\n",
+            TIMESTAMP
+        )
+    );
+}
+
+#[test]
+fn log_assert_panic_logs_before_panicking() {
+    let mut panicked = None;
+    let output = capture(builder(), || {
+        log_assert_panic!(1 + 1 == 2);
+        panicked = Some(std::panic::catch_unwind(|| log_assert_panic!(1 + 1 == 3, "math")).is_err());
+    });
+    assert_eq!(panicked, Some(true));
+    assert_eq!(output, format!("[ {} ERROR] assertion failed: 1 + 1 == 3: math\n", TIMESTAMP));
+}
+
+#[test]
+fn clog_macros_use_the_crate_name_as_the_target() {
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let mirrored = targets.clone();
+    let config = LoggerBuilder {
+        record_mirror: Some(Arc::new(move |record: &log::Record| {
+            mirrored.lock().unwrap().push(record.target().to_string())
+        })),
+        ..builder()
+    };
+    capture(config, || {
+        clog_info!("grouped");
+        log_info!("by module");
+    });
+    assert_eq!(*targets.lock().unwrap(), [env!("CARGO_PKG_NAME"), module_path!()]);
+
+    // Only the crate name counts as a project target
+    let config = LoggerBuilder {
+        only_project_logs: true,
+        ..builder()
+    };
+    let output = capture(config, || {
+        clog_info!("grouped");
+        log_info!("by module");
+    });
+    assert_eq!(output, format!("[ {} INFO]  grouped\n", TIMESTAMP));
+}
+
+#[test]
+fn lazy_macros_only_build_enabled_messages() {
+    let mut calls = 0;
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        ..builder()
+    };
+    let output = capture(config, || {
+        log_debug_lazy!(|| {
+            calls += 1;
+            "expensive"
+        });
+        assert!(!rimplog::enabled!(Level::Debug));
+        assert!(rimplog::enabled!(Level::Info));
+        log_info_lazy!(|| {
+            calls += 1;
+            "cheap enough"
+        });
+    });
+    assert_eq!(calls, 1);
+    assert_eq!(output, format!("[ {} INFO]  cheap enough\n", TIMESTAMP));
+}
+
+#[cfg(feature = "error-chain")]
+#[test]
+fn log_error_chain_logs_every_cause() {
+    #[derive(Debug)]
+    struct Layer(&'static str, Option<Box<Layer>>);
+
+    impl std::fmt::Display for Layer {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl std::error::Error for Layer {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1.as_deref().map(|cause| cause as _)
+        }
+    }
+
+    let err = Layer(
+        "failed to start server",
+        Some(Box::new(Layer("failed to read config.toml", Some(Box::new(Layer("permission denied", None)))))),
+    );
+    let output = capture(builder(), || rimplog::log_error_chain!(err));
+    assert_eq!(
+        output,
+        format!(
+            "[ {} ERROR] failed to start server\n  caused by: failed to read config.toml\n  caused by: permission denied\n",
+            TIMESTAMP
+        )
+    );
+}
+
+#[test]
+fn log_dbg_logs_the_expressions_and_returns_the_values() {
+    let width = 3;
+    let name = "tile";
+    let mut values = None;
+    let output = capture(builder(), || {
+        values = Some((log_dbg!(width * 4), log_dbg!(width, name)));
+    });
+    assert_eq!(values, Some((12, (3, "tile"))));
+    assert_eq!(
+        output,
+        format!("[ {0} DEBUG] width * 4 = 12\n[ {0} DEBUG] width = 3, name = \"tile\"\n", TIMESTAMP)
+    );
+
+    // The location is the macro call
+    let output = capture(builder().preset(rimplog::LoggerPreset::FULL), || {
+        log_dbg!(width);
+    });
+    assert!(output.contains(&format!("macros.rs:{}] width = 3\n", line!() - 2)), "{}", output);
+
+    let config = LoggerBuilder {
+        level: "info".to_string(),
+        ..builder()
+    };
+    let output = capture(config, || assert_eq!(log_dbg!(width + 1), 4));
+    assert_eq!(output, "");
+}
+
+#[test]
+fn explicitly_empty_messages_are_logged_as_empty_lines() {
+    let output = capture(builder(), || {
+        log_info!("");
+        rimplog::log_warn!("{}", "");
+    });
+    assert_eq!(output, format!("[ {0} INFO]\n[ {0} WARN]\n", TIMESTAMP));
+}
+
+#[test]
+fn with_correlation_tags_the_records_of_the_scope() {
+    let first = rimplog::new_correlation_id();
+    let second = rimplog::new_correlation_id();
+    assert!(second > first);
+    let output = capture(builder(), || {
+        log_info!("before");
+        rimplog::with_correlation!(first, {
+            log_info!("inside");
+            rimplog::with_correlation!(second, {
+                log_info!("nested");
+            });
+            log_info!("inside again");
+            // The id is per thread
+            std::thread::spawn(|| log_info!("other thread")).join().unwrap();
+        });
+        log_info!("after");
+    });
+    assert_eq!(
+        output,
+        format!(
+            "[ {0} INFO]  before\n[ {0} INFO]  [#{1}] inside\n[ {0} INFO]  [#{2}] nested\n\
+             [ {0} INFO]  [#{1}] inside again\n[ {0} INFO]  other thread\n[ {0} INFO]  after\n",
+            TIMESTAMP, first, second
+        )
+    );
+}
+
+#[test]
+fn log_throughput_logs_the_rate_of_the_block() {
+    let mut value = None;
+    let output = capture(builder(), || {
+        value = Some(log_throughput!("batch", 1000, {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            7
+        }));
+    });
+    assert_eq!(value, Some(7));
+    let prefix = format!("[ {} INFO]  batch: 1000 ops in ", TIMESTAMP);
+    let line = output.strip_prefix(&prefix).unwrap_or_else(|| panic!("{}", output));
+    let rate: f64 = line
+        .rsplit_once(" (")
+        .and_then(|(_, rate)| rate.strip_suffix("/s)\n"))
+        .unwrap_or_else(|| panic!("{}", output))
+        .parse()
+        .unwrap();
+    // At least 100ms for 1000 operations, so at most 10000 per second
+    assert!(rate > 100.0 && rate <= 10000.0, "{}", output);
+}
+
+#[test]
+fn log_at_time_shows_the_supplied_timestamp() {
+    let event_time = chrono::DateTime::parse_from_rfc3339("2019-06-01T12:00:05+08:00").unwrap();
+    let output = capture(builder(), || {
+        log_info_at_time!(event_time, "order {} shipped", 42);
+        log_info!("replay done");
+    });
+    // Only the replayed record has the supplied time, the next one is back to the clock
+    assert_eq!(
+        output,
+        format!("[ 2019-06-01 12:00:05 INFO]  order 42 shipped\n[ {} INFO]  replay done\n", TIMESTAMP)
+    );
+
+    // With the system clock too
+    let config = LoggerBuilder {
+        clock: LoggerBuilder::default().clock,
+        ..builder()
+    };
+    let output = capture(config, || log_info_at_time!(event_time, "old event"));
+    assert_eq!(output, "[ 2019-06-01 12:00:05 INFO]  old event\n");
+}
+
+#[test]
+fn log_line_is_not_interleaved_by_other_threads() {
+    const THREADS: usize = 8;
+    const FRAGMENTS: usize = 50;
+    let output = capture(builder(), || {
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                scope.spawn(move || {
+                    let mut line = log_line!(Level::Info, "t{}:", thread);
+                    for fragment in 0..FRAGMENTS {
+                        line.push(format_args!(" {}", fragment));
+                        std::thread::yield_now();
+                    }
+                });
+            }
+        });
+    });
+    let fragments: String = (0..FRAGMENTS).map(|fragment| format!(" {}", fragment)).collect();
+    let mut lines: Vec<&str> = output.lines().collect();
+    lines.sort();
+    let expected: Vec<String> = (0..THREADS).map(|thread| format!("[ {} INFO]  t{}:{}", TIMESTAMP, thread, fragments)).collect();
+    assert_eq!(lines, expected);
+}
+
+/// A helper logging on behalf of its caller
+#[track_caller]
+fn warn_deprecated(name: &str) {
+    rimplog::log_warn_caller!("{} is deprecated", name);
+}
+
+#[test]
+fn caller_macros_log_the_location_of_the_caller() {
+    let mut call_line = 0;
+    let output = capture(builder().preset(rimplog::LoggerPreset::FULL), || {
+        call_line = line!() + 1;
+        warn_deprecated("load_v1");
+    });
+    assert!(output.contains(&format!("macros.rs:{}] load_v1 is deprecated\n", call_line)), "{}", output);
+
+    // Without `#[track_caller]` on the helper, it's the helper's own line
+    fn untracked() {
+        rimplog::log_warn_caller!("untracked");
+    }
+    let helper_line = line!() - 2;
+    let output = capture(builder().preset(rimplog::LoggerPreset::FULL), untracked);
+    assert!(output.contains(&format!("macros.rs:{}] untracked\n", helper_line)), "{}", output);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::fs;
+
+use log::{Level, Log};
+use rimplog::{log_scoped, LoggerBuilder, NamedLogger};
+
+use common::{builder, TIMESTAMP};
+
+#[test]
+fn named_loggers_filter_independently() {
+    let verbose_path = common::temp_path("verbose.log");
+    let quiet_path = common::temp_path("quiet.log");
+    let verbose = NamedLogger::new(
+        "verbose",
+        LoggerBuilder {
+            level: "debug".to_string(),
+            file: Some(verbose_path.clone()),
+            ..builder()
+        },
+    );
+    let quiet = NamedLogger::new(
+        "quiet",
+        LoggerBuilder {
+            level: "warn".to_string(),
+            file: Some(quiet_path.clone()),
+            ..builder()
+        },
+    );
+    assert_eq!(verbose.name(), "verbose");
+
+    for logger in [&verbose, &quiet] {
+        log_scoped!(logger, Level::Debug, "details");
+        log_scoped!(logger, Level::Warn, "careful");
+        logger.flush();
+    }
+
+    let verbose_output = fs::read_to_string(&verbose_path).unwrap();
+    let quiet_output = fs::read_to_string(&quiet_path).unwrap();
+    let _ = fs::remove_file(verbose_path);
+    let _ = fs::remove_file(quiet_path);
+    assert_eq!(
+        verbose_output,
+        format!("[ {0} DEBUG] details\n[ {0} WARN]  careful\n", TIMESTAMP)
+    );
+    assert_eq!(quiet_output, format!("[ {} WARN]  careful\n", TIMESTAMP));
+}
@@ -0,0 +1,10 @@
+//! Compile errors of the macros, with the spans the user sees
+//!
+//! Run with `TRYBUILD=overwrite cargo test --test ui` to update the expected output after a
+//! compiler upgrade.
+
+#[test]
+fn macro_errors_point_at_the_call_site() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,3 @@
+fn main() {
+    rimplog::log_debug!();
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let (done, total) = (1, 2);
+    rimplog::log_warn!("{} done", done, total);
+}
@@ -0,0 +1,3 @@
+fn main() {
+    rimplog::log_error!("failed for {user}");
+}